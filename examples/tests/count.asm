@@ -0,0 +1,9 @@
+; Stores 0 to 15 at $4300 - $430F and stops
+LDX #$00
+store:
+TXA
+STA $4300,X
+INX
+CPX #$10
+BNE store
+BRK
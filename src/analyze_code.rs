@@ -1,5 +1,5 @@
 use parse_display::{Display, FromStr};
-use std::u16;
+use crate::system::system::{BANK_WINDOW_START, BANK_WINDOW_END};
 
 macro_rules! remove_whitespaces {
     ($to_remove: expr) => {
@@ -7,6 +7,7 @@ macro_rules! remove_whitespaces {
     };
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, Display, FromStr, PartialEq, Eq)]
 pub enum Opcode {
     ADC, AND, ASL, BCC, BCS, BEQ, BIT, BMI,
@@ -15,7 +16,7 @@ pub enum Opcode {
     INC, INX, INY, JMP, JSR, LDA, LDX, LDY,
     LSR, NOP, ORA, PHA, PLA, PHP, PLP, ROL, 
     ROR, RTS, SBC, SEC, SED, SEI, STA, STX, 
-    STY, TAX, TAY, TSX, TXA, TXS, TYA,
+    STY, TAX, TAY, TSX, TXA, TXS, TYA, RTI,
 
     LABEL, DRW
 }
@@ -43,10 +44,22 @@ pub struct Instruction {
     pub label_name: String
 }
 
+// A block of bytes placed in memory by the loader, blocks with a bank are loaded into that bank of the banked window
+#[derive(Clone, Debug)]
+pub struct DataBlock {
+    pub bank: Option<usize>,
+    pub address: u16,
+    pub bytes: Vec<u8>
+}
+
 pub fn get_instructions(instructions: Vec<String>) -> Vec<Instruction> {
     let mut to_return: Vec<Instruction> = Vec::new();
     
     for i in 0..instructions.len() {
+        if instructions[i].starts_with('.') {
+            continue;
+        }
+
         let opcode_str: &str = instructions[i][0..3].trim();
         let operand: &str = instructions[i][3..].trim();
         let opcode: Opcode = get_opcode(opcode_str, instructions[i].as_str());
@@ -79,8 +92,60 @@ pub fn get_instructions(instructions: Vec<String>) -> Vec<Instruction> {
     to_return
 }
 
+// Data directives:
+//  .org $XXXX          continue placing bytes at the given address, in the current bank if it is inside the banked window
+//  .bank N             continue placing bytes at the start of the banked window in bank N
+//  .byte $XX, $XX, ... place the bytes at the current position
+pub fn get_data_blocks(lines: Vec<String>) -> Vec<DataBlock> {
+    let mut to_return: Vec<DataBlock> = Vec::new();
+    let mut current_bank: Option<usize> = None;
+
+    for line in lines.iter().filter(|line| line.starts_with('.')) {
+        let (directive, operand) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        let operand: &str = operand.trim();
+
+        match directive {
+            ".org" => {
+                let address: u16 = u16::from_str_radix(operand.trim_start_matches('$'), 16).unwrap_or_else(|_| panic!("Invalid address in directive {line}"));
+
+                // Leaving the banked window goes back to placing data in plain memory
+                if !(BANK_WINDOW_START..=BANK_WINDOW_END).contains(&address) {
+                    current_bank = None;
+                }
+
+                to_return.push(DataBlock { bank: current_bank, address, bytes: vec![] });
+            },
+
+            ".bank" => {
+                current_bank = Some(operand.parse().unwrap_or_else(|_| panic!("Invalid bank number in directive {line}")));
+                to_return.push(DataBlock { bank: current_bank, address: BANK_WINDOW_START, bytes: vec![] });
+            },
+
+            ".byte" => {
+                let block: &mut DataBlock = match to_return.last_mut() {
+                    Some(block) => block,
+                    None => panic!("Directive {line} has no address to place its bytes at, use .org or .bank first")
+                };
+
+                for byte in remove_whitespaces!(operand).split(',') {
+                    block.bytes.push(u8::from_str_radix(byte.trim_start_matches('$'), 16).unwrap_or_else(|_| panic!("Invalid byte {byte} in directive {line}")));
+                }
+
+                if block.address as usize + block.bytes.len() > 0x10000 {
+                    panic!("Directive {line} places bytes past $FFFF");
+                }
+            },
+
+            _ => panic!("Unknown directive {line}")
+        }
+    }
+
+    to_return.retain(|block| !block.bytes.is_empty());
+    to_return
+}
+
 fn get_opcode(opcode_to_analyze: &str, original_version: &str) -> Opcode {
-    if original_version.ends_with(':') == false{
+    if !original_version.ends_with(':') {
         opcode_to_analyze.parse().unwrap()
     } else {
         Opcode::LABEL
@@ -165,9 +230,9 @@ fn get_operand_value(parameters: &str, addressing_mode: AddressingMode) -> u16 {
         match addressing_mode {
             AddressingMode::Immediate => &parameters[2..],
             AddressingMode::ZeroPage | AddressingMode::Absolute => &parameters[1..],
-            AddressingMode::ZeroPageX | AddressingMode::ZeroPageY | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => &parameters[1..].split(&",".to_string()).collect::<Vec<&str>>()[0],
-            AddressingMode::IndirectX => &parameters[2..].split(&",".to_string()).collect::<Vec<&str>>()[0],
-            AddressingMode::IndirectY => &parameters[2..].split(&")".to_string()).collect::<Vec<&str>>()[0],
+            AddressingMode::ZeroPageX | AddressingMode::ZeroPageY | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => parameters[1..].split(&",".to_string()).collect::<Vec<&str>>()[0],
+            AddressingMode::IndirectX => parameters[2..].split(&",".to_string()).collect::<Vec<&str>>()[0],
+            AddressingMode::IndirectY => parameters[2..].split(&")".to_string()).collect::<Vec<&str>>()[0],
             
             _ => "FFFF"
         }
@@ -0,0 +1,92 @@
+use crate::devices::sound::SAMPLE_RATE;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+// Writes 16 bit mono PCM, the sizes in the header are patched after every chunk so the file stays playable if the emulator is killed
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    data_size: u32
+}
+
+impl WavWriter {
+    pub fn create(path: String) -> WavWriter {
+        let mut wav_writer: WavWriter = WavWriter {
+            writer: BufWriter::new(File::create(path).expect("Error creating wav file")),
+            data_size: 0
+        };
+
+        wav_writer.write_header().expect("Error writing wav file");
+        wav_writer
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) {
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes()).expect("Error writing wav file");
+        }
+
+        self.data_size += samples.len() as u32 * 2;
+        self.update_sizes().expect("Error writing wav file");
+    }
+
+    fn update_sizes(&mut self) -> std::io::Result<()> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(36 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(b"RIFF")?;
+        self.writer.write_all(&(36 + self.data_size).to_le_bytes())?;
+        self.writer.write_all(b"WAVEfmt ")?;
+        self.writer.write_all(&16u32.to_le_bytes())?;
+        self.writer.write_all(&1u16.to_le_bytes())?;
+        self.writer.write_all(&1u16.to_le_bytes())?;
+        self.writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        self.writer.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        self.writer.write_all(&2u16.to_le_bytes())?;
+        self.writer.write_all(&16u16.to_le_bytes())?;
+        self.writer.write_all(b"data")?;
+        self.writer.write_all(&self.data_size.to_le_bytes())
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+// Samples beyond this many are dropped from the front, so the sound card does not fall further and further behind a slow frame rate
+#[cfg(feature = "audio")]
+const MAXIMUM_BUFFERED_SAMPLES: usize = SAMPLE_RATE as usize * 10;
+
+#[cfg(feature = "audio")]
+pub struct HostAudio {
+    _stream: rodio::OutputStream,
+    sink: rodio::Sink
+}
+
+#[cfg(feature = "audio")]
+impl HostAudio {
+    // Returns None when there is no sound card to play on, the emulator then runs silently
+    pub fn init() -> Option<HostAudio> {
+        let (stream, stream_handle) = rodio::OutputStream::try_default().ok()?;
+        let sink: rodio::Sink = rodio::Sink::try_new(&stream_handle).ok()?;
+
+        Some(HostAudio { _stream: stream, sink })
+    }
+
+    pub fn play_samples(&mut self, mut samples: Vec<i16>) {
+        if samples.len() > MAXIMUM_BUFFERED_SAMPLES {
+            let excess: usize = samples.len() - MAXIMUM_BUFFERED_SAMPLES;
+            samples.drain(0..excess);
+        }
+
+        if !samples.is_empty() {
+            self.sink.append(rodio::buffer::SamplesBuffer::new(1, SAMPLE_RATE, samples));
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use crate::analyze_code::Instruction;
+use crate::config;
+use crate::debugger;
+use crate::emulator::Vp8System;
+use crate::system::system::{AccessKind, MemoryAccess};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access
+}
+
+impl WatchKind {
+    pub fn parse(text: &str) -> Result<WatchKind, String> {
+        match text {
+            "read" => Ok(WatchKind::Read),
+            "write" => Ok(WatchKind::Write),
+            "access" => Ok(WatchKind::Access),
+
+            _ => Err(format!("Unknown watch kind {text}, expected read, write or access"))
+        }
+    }
+
+    fn matches(&self, kind: AccessKind) -> bool {
+        match self {
+            WatchKind::Read => kind == AccessKind::Read,
+            WatchKind::Write => kind == AccessKind::Write,
+            WatchKind::Access => true
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Acc,
+    X,
+    Y,
+    Sp,
+    Memory(u16)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual
+}
+
+// Written as `register comparison value` or `$XXXX comparison value`, for example `X == $40` or `$0200 >= 16`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    operand: Operand,
+    comparison: Comparison,
+    value: u8,
+    text: String
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+
+        if parts.len() != 3 {
+            return Err(format!("Invalid condition {text}, expected for example X == $40"));
+        }
+
+        let operand: Operand = match parts[0].to_ascii_uppercase().as_str() {
+            "A" => Operand::Acc,
+            "X" => Operand::X,
+            "Y" => Operand::Y,
+            "SP" => Operand::Sp,
+
+            other if other.starts_with('$') => Operand::Memory(config::parse_address(other)?),
+            other => return Err(format!("Unknown register {other} in condition, expected A, X, Y, SP or a $XXXX address"))
+        };
+
+        let comparison: Comparison = match parts[1] {
+            "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+
+            other => return Err(format!("Unknown comparison {other} in condition"))
+        };
+
+        Ok(Condition {
+            operand,
+            comparison,
+            value: parse_byte(parts[2])?,
+            text: parts.join(" ")
+        })
+    }
+
+    pub fn is_met(&self, vp8: &Vp8System) -> bool {
+        let current: u8 = match self.operand {
+            Operand::Acc => vp8.registers.get_acc(),
+            Operand::X => vp8.registers.get_x(),
+            Operand::Y => vp8.registers.get_y(),
+            Operand::Sp => vp8.registers.get_sp(),
+            Operand::Memory(address) => vp8.memory.peek_mem_cell_value(address as usize)
+        };
+
+        match self.comparison {
+            Comparison::Equal => current == self.value,
+            Comparison::NotEqual => current != self.value,
+            Comparison::Less => current < self.value,
+            Comparison::LessOrEqual => current <= self.value,
+            Comparison::Greater => current > self.value,
+            Comparison::GreaterOrEqual => current >= self.value
+        }
+    }
+}
+
+// Bytes are hex with a $ or decimal without one
+pub fn parse_byte(text: &str) -> Result<u8, String> {
+    match text.strip_prefix('$') {
+        Some(digits) => u8::from_str_radix(digits, 16),
+        None => text.parse()
+    }.map_err(|_| format!("Invalid byte {text}, expected $XX or 0 to 255"))
+}
+
+// Stops before the instruction runs, once the condition held on more than ignore_count arrivals
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub index: usize,
+    pub condition: Option<Condition>,
+    pub ignore_count: u64,
+    pub temporary: bool,
+    pub hits: u64
+}
+
+// Stops after an instruction that accessed the range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+    pub condition: Option<Condition>,
+    pub hits: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Point {
+    Break(Breakpoint),
+    Watch(Watchpoint)
+}
+
+// Why a run stopped, with what is needed to report it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    Breakpoint { id: usize, index: usize, hits: u64 },
+    Watchpoint { id: usize, executed_index: usize, access: MemoryAccess }
+}
+
+// The breakpoints and watchpoints of a debugging session, numbered from 1 in the order they were added
+pub struct Breakpoints {
+    points: Vec<(usize, Point)>,
+    next_id: usize
+}
+
+impl Breakpoints {
+    pub fn init() -> Breakpoints {
+        Breakpoints {
+            points: vec![],
+            next_id: 1
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, index: usize, condition: Option<Condition>, ignore_count: u64, temporary: bool) -> usize {
+        self.add(Point::Break(Breakpoint { index, condition, ignore_count, temporary, hits: 0 }))
+    }
+
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, kind: WatchKind, condition: Option<Condition>) -> usize {
+        self.add(Point::Watch(Watchpoint { start, end, kind, condition, hits: 0 }))
+    }
+
+    fn add(&mut self, point: Point) -> usize {
+        let id: usize = self.next_id;
+
+        self.points.push((id, point));
+        self.next_id += 1;
+
+        id
+    }
+
+    // Returns whether there was a breakpoint or watchpoint with the number
+    pub fn delete(&mut self, id: usize) -> bool {
+        let count: usize = self.points.len();
+
+        self.points.retain(|(other, _)| *other != id);
+        self.points.len() != count
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn has_watchpoints(&self) -> bool {
+        self.points.iter().any(|(_, point)| matches!(point, Point::Watch(_)))
+    }
+
+    // Checked after every instruction with the instruction that ran, its memory accesses and the instruction that runs next.
+    // A temporary breakpoint is deleted once it stops a run
+    pub fn check(&mut self, executed_index: usize, accesses: &[MemoryAccess], next_index: usize, vp8: &Vp8System) -> Vec<Hit> {
+        let mut hits: Vec<Hit> = vec![];
+        let mut finished: Vec<usize> = vec![];
+
+        for (id, point) in self.points.iter_mut() {
+            match point {
+                Point::Break(breakpoint) => {
+                    if breakpoint.index != next_index || !breakpoint.condition.as_ref().map_or(true, |condition| condition.is_met(vp8)) {
+                        continue;
+                    }
+
+                    breakpoint.hits += 1;
+
+                    if breakpoint.hits > breakpoint.ignore_count {
+                        hits.push(Hit::Breakpoint { id: *id, index: next_index, hits: breakpoint.hits });
+
+                        if breakpoint.temporary {
+                            finished.push(*id);
+                        }
+                    }
+                }
+
+                Point::Watch(watchpoint) => {
+                    for access in accesses.iter().filter(|access| access.address >= watchpoint.start && access.address <= watchpoint.end && watchpoint.kind.matches(access.kind)) {
+                        if watchpoint.condition.as_ref().map_or(true, |condition| condition.is_met(vp8)) {
+                            watchpoint.hits += 1;
+                            hits.push(Hit::Watchpoint { id: *id, executed_index, access: *access });
+                        }
+                    }
+                }
+            }
+        }
+
+        for id in finished {
+            self.delete(id);
+        }
+
+        hits
+    }
+
+    // One line per breakpoint or watchpoint for the monitor
+    pub fn describe(&self, instructions: &[Instruction]) -> Vec<String> {
+        self.points.iter().map(|(id, point)| {
+            let (mut description, condition, hits): (String, &Option<Condition>, u64) = match point {
+                Point::Break(breakpoint) => {
+                    let mut description: String = format!("{id:>3}  {} at {} {}", if breakpoint.temporary { "temporary breakpoint" } else { "breakpoint" }, breakpoint.index, debugger::instruction_text(&instructions[breakpoint.index]));
+
+                    if breakpoint.ignore_count > 0 {
+                        description += &format!(" after {}", breakpoint.ignore_count);
+                    }
+
+                    (description, &breakpoint.condition, breakpoint.hits)
+                }
+
+                Point::Watch(watchpoint) => {
+                    let kind: &str = match watchpoint.kind {
+                        WatchKind::Read => "read",
+                        WatchKind::Write => "write",
+                        WatchKind::Access => "access"
+                    };
+
+                    (format!("{id:>3}  {kind} watchpoint on {}", format_range(watchpoint.start, watchpoint.end)), &watchpoint.condition, watchpoint.hits)
+                }
+            };
+
+            if let Some(condition) = condition {
+                description += &format!(" if {}", condition.text);
+            }
+
+            format!("{description}, hit count {hits}")
+        }).collect()
+    }
+}
+
+pub fn format_hit(hit: &Hit, instructions: &[Instruction]) -> String {
+    match hit {
+        Hit::Breakpoint { id, index, hits } => format!("Breakpoint {id} at {index} {}, hit count {hits}", debugger::instruction_text(&instructions[*index])),
+        Hit::Watchpoint { id, executed_index, access } => {
+            let instruction: String = debugger::instruction_text(&instructions[*executed_index]);
+
+            match access.kind {
+                AccessKind::Read => format!("Watchpoint {id}: {executed_index} {instruction} read ${:04X} = ${:02X}", access.address, access.new_value),
+                AccessKind::Write => format!("Watchpoint {id}: {executed_index} {instruction} wrote ${:04X}, ${:02X} -> ${:02X}", access.address, access.old_value, access.new_value)
+            }
+        }
+    }
+}
+
+fn format_range(start: u16, end: u16) -> String {
+    if start == end {
+        format!("${start:04X}")
+    } else {
+        format!("${start:04X}-${end:04X}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::system::{self, ScreenLayout};
+
+    fn test_system() -> Vp8System {
+        Vp8System {
+            registers: system::Registers::init(),
+            memory: system::Memory::init(0, ScreenLayout { width: system::DEFAULT_SCREEN_WIDTH, height: system::DEFAULT_SCREEN_HEIGHT, start_address: system::DEFAULT_SCREEN_MEMORY_START }),
+            flags: system::Flags::init(),
+            interrupt_returns: vec![],
+            cycles: 0
+        }
+    }
+
+    fn access(address: u16, kind: AccessKind) -> MemoryAccess {
+        MemoryAccess { address, kind, old_value: 0, new_value: 1 }
+    }
+
+    #[test]
+    fn conditions_parse_registers_addresses_and_comparisons() {
+        assert_eq!(Condition::parse("x == $40"), Ok(Condition { operand: Operand::X, comparison: Comparison::Equal, value: 0x40, text: "x == $40".to_string() }));
+        assert_eq!(Condition::parse("SP  <=  16").map(|condition| (condition.operand, condition.comparison, condition.value)), Ok((Operand::Sp, Comparison::LessOrEqual, 16)));
+        assert_eq!(Condition::parse("$0200 != $FF").map(|condition| (condition.operand, condition.comparison)), Ok((Operand::Memory(0x0200), Comparison::NotEqual)));
+
+        assert!(Condition::parse("X == ").is_err());
+        assert!(Condition::parse("Z == 1").is_err());
+        assert!(Condition::parse("A = 1").is_err());
+        assert!(Condition::parse("A == 256").is_err());
+        assert!(Condition::parse("A == 1 2").is_err());
+    }
+
+    #[test]
+    fn conditions_compare_the_current_value() {
+        let mut vp8: Vp8System = test_system();
+
+        vp8.registers.set_y(5);
+        vp8.memory.set_mem_cell_value(0x0300, 0x80);
+
+        assert!(Condition::parse("Y > 4").unwrap().is_met(&vp8));
+        assert!(!Condition::parse("Y < 5").unwrap().is_met(&vp8));
+        assert!(Condition::parse("$0300 >= $80").unwrap().is_met(&vp8));
+    }
+
+    #[test]
+    fn breakpoints_stop_after_their_ignore_count() {
+        let vp8: Vp8System = test_system();
+        let mut breakpoints: Breakpoints = Breakpoints::init();
+        let id: usize = breakpoints.add_breakpoint(3, None, 2, false);
+
+        assert!(breakpoints.check(2, &[], 3, &vp8).is_empty());
+        assert!(breakpoints.check(2, &[], 3, &vp8).is_empty());
+        assert!(breakpoints.check(2, &[], 4, &vp8).is_empty());
+        assert_eq!(breakpoints.check(2, &[], 3, &vp8), vec![Hit::Breakpoint { id, index: 3, hits: 3 }]);
+        assert_eq!(breakpoints.check(2, &[], 3, &vp8), vec![Hit::Breakpoint { id, index: 3, hits: 4 }]);
+    }
+
+    #[test]
+    fn temporary_breakpoints_are_deleted_once_they_stop() {
+        let mut vp8: Vp8System = test_system();
+        let mut breakpoints: Breakpoints = Breakpoints::init();
+        let temporary: usize = breakpoints.add_breakpoint(1, Some(Condition::parse("A == 1").unwrap()), 0, true);
+
+        // A breakpoint whose condition does not hold does not stop, so it is kept for the next arrival
+        assert!(breakpoints.check(0, &[], 1, &vp8).is_empty());
+
+        vp8.registers.set_acc(1);
+
+        assert_eq!(breakpoints.check(0, &[], 1, &vp8), vec![Hit::Breakpoint { id: temporary, index: 1, hits: 1 }]);
+        assert!(breakpoints.check(0, &[], 1, &vp8).is_empty());
+        assert!(!breakpoints.delete(temporary));
+    }
+
+    #[test]
+    fn watchpoints_match_the_kind_of_access() {
+        let vp8: Vp8System = test_system();
+        let mut breakpoints: Breakpoints = Breakpoints::init();
+        let read: usize = breakpoints.add_watchpoint(0x0200, 0x020f, WatchKind::Read, None);
+        let write: usize = breakpoints.add_watchpoint(0x0200, 0x0200, WatchKind::Write, None);
+        let any: usize = breakpoints.add_watchpoint(0x0208, 0x0210, WatchKind::Access, None);
+
+        assert!(breakpoints.has_watchpoints());
+        assert_eq!(breakpoints.check(7, &[access(0x0200, AccessKind::Read)], 8, &vp8), vec![Hit::Watchpoint { id: read, executed_index: 7, access: access(0x0200, AccessKind::Read) }]);
+        assert_eq!(breakpoints.check(7, &[access(0x0200, AccessKind::Write)], 8, &vp8), vec![Hit::Watchpoint { id: write, executed_index: 7, access: access(0x0200, AccessKind::Write) }]);
+        assert_eq!(breakpoints.check(7, &[access(0x0210, AccessKind::Write)], 8, &vp8), vec![Hit::Watchpoint { id: any, executed_index: 7, access: access(0x0210, AccessKind::Write) }]);
+        assert_eq!(breakpoints.check(7, &[access(0x0208, AccessKind::Read)], 8, &vp8).len(), 2);
+        assert!(breakpoints.check(7, &[access(0x0211, AccessKind::Read), access(0x01ff, AccessKind::Write)], 8, &vp8).is_empty());
+
+        assert!(breakpoints.delete(read));
+        assert!(breakpoints.delete(write));
+        assert!(breakpoints.delete(any));
+        assert!(!breakpoints.has_watchpoints());
+    }
+}
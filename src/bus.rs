@@ -0,0 +1,117 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// The CPU only ever talks to memory through this trait, the address space behind it decides which device answers
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+
+    // Reads without side effects, used by the renderer so that drawing the screen does not trigger device behaviour
+    fn peek(&self, address: u16) -> u8;
+}
+
+// Devices receive addresses relative to the start of the range they are attached to
+pub trait Device {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, value: u8);
+    fn peek(&self, offset: u16) -> u8;
+
+    // Devices that can interrupt the CPU keep this true for as long as their interrupt has not been acknowledged
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Called after every instruction with the number of CPU cycles it took, for devices that run on the CPU clock
+    fn tick(&mut self, _cycles: u32) { }
+}
+
+// Lets the emulator keep a handle to a device after attaching it, so the host side (window, input scripts) can drive it
+impl<T: Device> Device for Rc<RefCell<T>> {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.borrow_mut().read(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.borrow_mut().write(offset, value);
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.borrow().peek(offset)
+    }
+
+    fn irq(&self) -> bool {
+        self.borrow().irq()
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.borrow_mut().tick(cycles);
+    }
+}
+
+pub struct MappedDevice {
+    start: u16,
+    end: u16,
+    device: Box<dyn Device>
+}
+
+pub struct Mapper {
+    devices: Vec<MappedDevice>
+}
+
+impl Mapper {
+    pub fn init() -> Mapper {
+        Mapper {
+            devices: vec![]
+        }
+    }
+
+    // Devices attached later take priority over the ones already covering the same addresses
+    pub fn attach(&mut self, start: u16, end: u16, device: Box<dyn Device>) {
+        if start > end {
+            panic!("Invalid device range ${:04X} - ${:04X}", start, end);
+        }
+
+        self.devices.push(MappedDevice { start, end, device });
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.devices.iter().any(|mapped| mapped.device.irq())
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.tick(cycles);
+        }
+    }
+
+    fn find_device(&self, address: u16) -> Option<usize> {
+        self.devices.iter().rposition(|mapped| address >= mapped.start && address <= mapped.end)
+    }
+}
+
+impl Bus for Mapper {
+    fn read(&mut self, address: u16) -> u8 {
+        match self.find_device(address) {
+            Some(index) => {
+                let mapped: &mut MappedDevice = &mut self.devices[index];
+                mapped.device.read(address - mapped.start)
+            },
+
+            None => 0
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.find_device(address) {
+            let mapped: &mut MappedDevice = &mut self.devices[index];
+            mapped.device.write(address - mapped.start, value);
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        match self.find_device(address) {
+            Some(index) => self.devices[index].device.peek(address - self.devices[index].start),
+            None => 0
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use parse_display::{Display, FromStr};
+use crate::devices::{joystick, keyboard};
+use crate::system::system::{self, ScreenLayout};
+use std::fs;
+
+#[derive(Debug, Clone, Copy, Display, FromStr, PartialEq, Eq)]
+#[display(style = "lowercase")]
+pub enum RomWriteMode {
+    Ignore,
+    Trap
+}
+
+// When a headless run saves a screenshot: after the given number of frames, or at the first instruction boundary at or after the given cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotTime {
+    Frame(u64),
+    Cycle(u64)
+}
+
+// The time of a screenshot and the path it is saved to
+pub type Screenshot = (ScreenshotTime, String);
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub file_path: String,
+    pub rom_regions: Vec<(u16, u16)>,
+    pub rom_write_mode: RomWriteMode,
+    pub banks: usize,
+    pub bank_image: Option<String>,
+    pub headless: bool,
+    pub debug: bool,
+    pub gdb: Option<String>,
+    pub frames: Option<u64>,
+    pub cycles: Option<u64>,
+    pub input_script: Option<String>,
+    pub record_input: Option<String>,
+    pub seed: Option<u64>,
+    pub joystick_bindings: [u8; 8],
+    pub wav: Option<String>,
+    pub serial_out: Option<String>,
+    pub serial_in: Option<String>,
+    // Not an option, the test runner turns it off so every test gets the same empty serial input
+    pub serial_stdin: bool,
+    pub file_sandbox: Option<String>,
+    pub blit_cycles_per_byte: u32,
+    pub refresh_rate: u32,
+    pub screen_layout: ScreenLayout,
+    pub pixel_scale: usize,
+    pub screenshots: Vec<Screenshot>,
+    pub record: Option<String>,
+    pub record_raw: Option<String>
+}
+
+impl Config {
+    pub fn init() -> Config {
+        Config {
+            file_path: String::new(),
+            rom_regions: vec![],
+            rom_write_mode: RomWriteMode::Ignore,
+            banks: 0,
+            bank_image: None,
+            headless: false,
+            debug: false,
+            gdb: None,
+            frames: None,
+            cycles: None,
+            input_script: None,
+            record_input: None,
+            seed: None,
+            joystick_bindings: joystick::DEFAULT_BINDINGS,
+            wav: None,
+            serial_out: None,
+            serial_in: None,
+            serial_stdin: true,
+            file_sandbox: None,
+            blit_cycles_per_byte: 1,
+            refresh_rate: 60,
+            screen_layout: ScreenLayout {
+                width: system::DEFAULT_SCREEN_WIDTH,
+                height: system::DEFAULT_SCREEN_HEIGHT,
+                start_address: system::DEFAULT_SCREEN_MEMORY_START
+            },
+            pixel_scale: 4,
+            screenshots: vec![],
+            record: None,
+            record_raw: None
+        }
+    }
+}
+
+// Options that take no value on the command line, in a config file they are written as `name = true` or `name = false`
+const SWITCHES: [&str; 2] = ["headless", "debug"];
+
+// Options that take two values on the command line, in a config file both are written after the = separated by a space
+const PAIRED_OPTIONS: [&str; 1] = ["screenshot-at"];
+
+// Keeps the stall of the largest blitter rectangle, 255 rows of 65535 bytes, inside the u32 cycle count of an instruction
+const MAX_BLIT_CYCLES_PER_BYTE: u32 = 255;
+
+// Every option can be given either as `--name value` on the command line or as `name = value` in the file passed to --config,
+// config files are applied in the order they are given and the options on the command line after them so they override them
+pub fn parse_arguments(arguments: Vec<String>) -> Result<Config, String> {
+    let mut config: Config = Config::init();
+    let mut file_options: Vec<(String, String)> = vec![];
+    let mut options: Vec<(String, String)> = vec![];
+    let mut index: usize = 1;
+
+    while index < arguments.len() {
+        let argument: &str = arguments[index].as_str();
+
+        if let Some(name) = argument.strip_prefix("--") {
+            if SWITCHES.contains(&name) {
+                options.push((name.to_string(), "true".to_string()));
+                index += 1;
+                continue;
+            }
+
+            if PAIRED_OPTIONS.contains(&name) {
+                if index + 2 >= arguments.len() {
+                    return Err(format!("Option --{name} is missing its values"));
+                }
+
+                options.push((name.to_string(), format!("{} {}", arguments[index + 1], arguments[index + 2])));
+                index += 3;
+                continue;
+            }
+
+            if index + 1 >= arguments.len() {
+                return Err(format!("Option --{name} is missing its value"));
+            }
+
+            if name == "config" {
+                file_options.extend(read_config_file(arguments[index + 1].as_str())?);
+            } else {
+                options.push((name.to_string(), arguments[index + 1].clone()));
+            }
+
+            index += 2;
+            continue;
+        }
+
+        if !config.file_path.is_empty() {
+            return Err(format!("Unexpected argument {argument}, only one input file can be given"));
+        }
+
+        config.file_path = argument.to_string();
+        index += 1;
+    }
+
+    for (name, value) in file_options.iter().chain(options.iter()) {
+        apply_option(&mut config, name, value)?;
+    }
+
+    if config.file_path.is_empty() {
+        return Err("Please input a path to the file".to_string());
+    }
+
+    check_screen_layout(&config)?;
+
+    Ok(config)
+}
+
+// The screen has to fit in memory and stay clear of the regions with a fixed place, the bank window of .bank directives is checked by the loader
+pub fn check_screen_layout(config: &Config) -> Result<(), String> {
+    let screen_layout: ScreenLayout = config.screen_layout;
+
+    if screen_layout.start_address as usize + screen_layout.size() > 0x10000 {
+        return Err(format!("A {}x{} screen does not fit in memory from ${:04X}", screen_layout.width, screen_layout.height, screen_layout.start_address));
+    }
+
+    match screen_layout.overlapped_region(config.banks > 0 || config.bank_image.is_some()) {
+        Some((name, start, end)) => Err(format!("The screen at ${:04X}-${:04X} overlaps {name} at ${start:04X}-${end:04X}", screen_layout.start_address, screen_layout.end_address())),
+        None => Ok(())
+    }
+}
+
+pub fn parse_address(value: &str) -> Result<u16, String> {
+    let digits: &str = value.trim().strip_prefix('$').unwrap_or(value.trim());
+
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid address {value}, addresses are written as $XXXX"))
+}
+
+// Written as `frame path` or `cycle:number path`, for example `120 title.png` or `cycle:50000 title.png`
+fn parse_screenshot(value: &str) -> Result<Screenshot, String> {
+    let (time, path): (&str, &str) = value.split_once(' ').ok_or(format!("Invalid screenshot {value}, expected a frame or cycle and a path"))?;
+
+    let time: ScreenshotTime = match time.strip_prefix("cycle:") {
+        Some(cycle) => ScreenshotTime::Cycle(cycle.parse().map_err(|_| format!("Invalid screenshot cycle {cycle}"))?),
+        None => ScreenshotTime::Frame(time.strip_prefix("frame:").unwrap_or(time).parse().map_err(|_| format!("Invalid screenshot frame {time}"))?)
+    };
+
+    Ok((time, path.trim().to_string()))
+}
+
+// Mouse coordinates are single bytes, so the screen can be at most 256 pixels in each direction
+fn parse_screen_dimension(value: &str) -> Result<usize, String> {
+    value.parse().ok().filter(|dimension| (1..=256).contains(dimension)).ok_or(format!("Invalid screen dimension {value}, expected 1 to 256"))
+}
+
+pub fn parse_address_range(value: &str) -> Result<(u16, u16), String> {
+    let bounds: Vec<&str> = value.split('-').collect();
+
+    if bounds.len() != 2 {
+        return Err(format!("Invalid address range {value}, ranges are written as $XXXX-$XXXX"));
+    }
+
+    let start: u16 = parse_address(bounds[0])?;
+    let end: u16 = parse_address(bounds[1])?;
+
+    if start > end {
+        return Err(format!("Invalid address range {value}, the start address is after the end address"));
+    }
+
+    Ok((start, end))
+}
+
+pub fn parse_switch(name: &str, value: &str) -> Result<bool, String> {
+    value.parse().map_err(|_| format!("Invalid value {value} for {name}, expected true or false"))
+}
+
+pub fn read_config_file(path: &str) -> Result<Vec<(String, String)>, String> {
+    let file_data: String = fs::read_to_string(path).map_err(|error| format!("Error reading config file {path}: {error}"))?;
+    let mut to_return: Vec<(String, String)> = vec![];
+
+    for line in file_data.lines() {
+        let line: &str = line.split(';').next().unwrap_or("").trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((name, value)) => to_return.push((name.trim().to_string(), value.trim().to_string())),
+            None => return Err(format!("Invalid line in config file {path}: {line}"))
+        }
+    }
+
+    Ok(to_return)
+}
+
+pub fn apply_option(config: &mut Config, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "rom" => config.rom_regions.push(parse_address_range(value)?),
+        "rom-writes" => config.rom_write_mode = value.parse().map_err(|_| format!("Invalid value {value} for rom-writes, expected ignore or trap"))?,
+        "banks" => config.banks = value.parse().map_err(|_| format!("Invalid bank count {value}"))?,
+        "bank-image" => config.bank_image = Some(value.to_string()),
+        "headless" => config.headless = parse_switch(name, value)?,
+        "debug" => config.debug = parse_switch(name, value)?,
+        "gdb" => config.gdb = Some(value.to_string()),
+        "frames" => config.frames = Some(value.parse().map_err(|_| format!("Invalid frame count {value}"))?),
+        "cycles" => config.cycles = Some(value.parse().map_err(|_| format!("Invalid cycle count {value}"))?),
+        "input-script" => config.input_script = Some(value.to_string()),
+        "record-input" => config.record_input = Some(value.to_string()),
+        "refresh-rate" => config.refresh_rate = value.parse().ok().filter(|rate| *rate > 0).ok_or(format!("Invalid refresh rate {value}"))?,
+        "screen-width" => config.screen_layout.width = parse_screen_dimension(value)?,
+        "screen-height" => config.screen_layout.height = parse_screen_dimension(value)?,
+        "screen-address" => config.screen_layout.start_address = parse_address(value)?,
+        "pixel-scale" => config.pixel_scale = value.parse().ok().filter(|scale| *scale > 0).ok_or(format!("Invalid pixel scale {value}"))?,
+        "screenshot-at" => config.screenshots.push(parse_screenshot(value)?),
+        "record" => config.record = Some(value.to_string()),
+        "record-raw" => config.record_raw = Some(value.to_string()),
+        "wav" => config.wav = Some(value.to_string()),
+        "serial-out" => config.serial_out = Some(value.to_string()),
+        "serial-in" => config.serial_in = Some(value.to_string()),
+        "file-sandbox" => config.file_sandbox = Some(value.to_string()),
+        "blit-cycles" => config.blit_cycles_per_byte = value.parse().ok().filter(|cycles| *cycles <= MAX_BLIT_CYCLES_PER_BYTE).ok_or(format!("Invalid blit cycle count {value}, expected 0 to {MAX_BLIT_CYCLES_PER_BYTE}"))?,
+        "seed" => config.seed = Some(value.parse().map_err(|_| format!("Invalid seed {value}"))?),
+
+        _ => {
+            let button: Option<usize> = name.strip_prefix("joystick-").and_then(|button_name| joystick::BUTTON_NAMES.iter().position(|other| *other == button_name));
+
+            match button {
+                Some(button) => config.joystick_bindings[button] = keyboard::key_code_from_name(value).ok_or(format!("Unknown key {value} for {name}"))?,
+                None => return Err(format!("Unknown option {name}"))
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,352 @@
+use crate::analyze_code::{Instruction, Opcode};
+use crate::breakpoints::{Breakpoints, Condition, WatchKind};
+use crate::config;
+use crate::emulator::Vp8System;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+step [count]              s  run one or more instructions
+next                      n  run one instruction, or a whole routine when it is a JSR
+finish                    f  run until the current routine or interrupt handler returns
+continue                  c  run until the program stops
+until <location>          u  run until the instruction at the location
+regs                      r  show the registers and flags
+mem <address> [length]    m  dump memory in hex and ASCII
+set <address> <bytes...>  w  write bytes to memory
+dis [location] [count]    d  disassemble around the current instruction or from a location
+labels [filter]           l  list the labels and the instructions they point to
+break <location> [after <count>] [if <condition>]  b  stop before the instruction, after skipping count hits
+tbreak <location> [if <condition>]  stop before the instruction once and then delete the breakpoint
+watch <range> [read|write|access] [if <condition>]  stop after an instruction accessed memory, write by default
+breaks                       list the breakpoints and watchpoints with their hits
+delete [number]              delete a breakpoint or watchpoint, or all of them
+quit                      q  stop the emulator
+Locations are label names or instruction numbers, addresses and bytes are hex with an optional $,
+ranges are $XXXX or $XXXX-$XXXX, conditions compare A, X, Y, SP or a $XXXX address with a byte, for example X == $40,
+an empty line repeats the last command";
+
+const DEFAULT_DUMP_LENGTH: usize = 64;
+const DEFAULT_DISASSEMBLY_LENGTH: usize = 16;
+
+// When a run started from the monitor stops, BRK always stops it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCondition {
+    Steps(u64),
+    // The routine and interrupt nesting are back to at most these depths, which steps over or out of routines
+    Depth { routines: usize, interrupts: usize },
+    Index(usize),
+    Never
+}
+
+impl StopCondition {
+    // Checked after every instruction
+    pub fn is_reached(&mut self, index: usize, routine_depth: usize, interrupt_depth: usize) -> bool {
+        match self {
+            StopCondition::Steps(steps) => {
+                *steps = steps.saturating_sub(1);
+                *steps == 0
+            }
+            StopCondition::Depth { routines, interrupts } => routine_depth <= *routines && interrupt_depth <= *interrupts,
+            StopCondition::Index(target) => index == *target,
+            StopCondition::Never => false
+        }
+    }
+}
+
+// What the monitor shows, next to the machine itself
+pub struct DebugView<'a> {
+    pub instructions: &'a [Instruction],
+    pub labels: &'a [(String, usize)],
+    pub index: usize,
+    pub routine_depth: usize,
+    pub frame: u64
+}
+
+pub enum MonitorAction {
+    Run(StopCondition),
+    Quit
+}
+
+// The prompt of --debug, it reads commands from stdin
+pub struct Monitor {
+    pub breakpoints: Breakpoints,
+    last_command: String
+}
+
+impl Monitor {
+    pub fn init() -> Monitor {
+        println!("VP8 monitor, type help for the commands");
+
+        Monitor {
+            breakpoints: Breakpoints::init(),
+            last_command: String::new()
+        }
+    }
+
+    // Reads commands until one of them runs the program, the commands that only look at or change the machine are handled here
+    pub fn next_action(&mut self, view: &DebugView, vp8: &mut Vp8System) -> MonitorAction {
+        loop {
+            print!("(vp8) ");
+            let _ = io::stdout().flush();
+
+            let mut line: String = String::new();
+
+            match io::stdin().lock().read_line(&mut line) {
+                Ok(0) | Err(_) => return MonitorAction::Quit,
+                Ok(_) => { }
+            }
+
+            let mut line: String = line.trim().to_string();
+
+            if line.is_empty() {
+                line = self.last_command.clone();
+            } else {
+                self.last_command = line.clone();
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            if parts.is_empty() {
+                continue;
+            }
+
+            match run_command(&parts, view, vp8, &mut self.breakpoints) {
+                Ok(Some(action)) => return action,
+                Ok(None) => { }
+                Err(message) => println!("{message}")
+            }
+        }
+    }
+}
+
+fn run_command(parts: &[&str], view: &DebugView, vp8: &mut Vp8System, breakpoints: &mut Breakpoints) -> Result<Option<MonitorAction>, String> {
+    let argument = |position: usize| parts.get(position).copied();
+
+    match parts[0] {
+        "step" | "s" => {
+            let steps: u64 = match argument(1) {
+                Some(count) => count.parse().ok().filter(|count| *count > 0).ok_or(format!("Invalid step count {count}"))?,
+                None => 1
+            };
+
+            Ok(Some(MonitorAction::Run(StopCondition::Steps(steps))))
+        }
+        "next" | "n" => {
+            if view.instructions[view.index].opcode == Opcode::JSR {
+                Ok(Some(MonitorAction::Run(StopCondition::Depth { routines: view.routine_depth, interrupts: vp8.interrupt_returns.len() })))
+            } else {
+                Ok(Some(MonitorAction::Run(StopCondition::Steps(1))))
+            }
+        }
+        "finish" | "f" => {
+            if view.routine_depth > 0 {
+                Ok(Some(MonitorAction::Run(StopCondition::Depth { routines: view.routine_depth - 1, interrupts: vp8.interrupt_returns.len() })))
+            } else if !vp8.interrupt_returns.is_empty() {
+                Ok(Some(MonitorAction::Run(StopCondition::Depth { routines: 0, interrupts: vp8.interrupt_returns.len() - 1 })))
+            } else {
+                Err("Not inside a routine or an interrupt handler".to_string())
+            }
+        }
+        "continue" | "c" => Ok(Some(MonitorAction::Run(StopCondition::Never))),
+        "until" | "u" => {
+            let location: &str = argument(1).ok_or("until needs a location")?;
+
+            Ok(Some(MonitorAction::Run(StopCondition::Index(parse_stop_location(location, view)?))))
+        }
+        "regs" | "r" => {
+            println!("{}", format_registers(view, vp8));
+            Ok(None)
+        }
+        "mem" | "m" => {
+            let start: u16 = config::parse_address(argument(1).ok_or("mem needs an address")?)?;
+            let length: usize = match argument(2) {
+                Some(length) => length.parse().map_err(|_| format!("Invalid length {length}"))?,
+                None => DEFAULT_DUMP_LENGTH
+            };
+
+            print!("{}", format_memory_dump(vp8, start, length));
+            Ok(None)
+        }
+        "set" | "w" => {
+            let start: u16 = config::parse_address(argument(1).ok_or("set needs an address")?)?;
+
+            if parts.len() < 3 {
+                return Err("set needs the bytes to write".to_string());
+            }
+
+            for (offset, byte) in parts[2..].iter().enumerate() {
+                let value: u8 = u8::from_str_radix(byte.trim_start_matches('$'), 16).map_err(|_| format!("Invalid byte {byte}"))?;
+                let address: u16 = start.wrapping_add(offset as u16);
+
+                vp8.memory.set_mem_cell_value(address as usize, value);
+
+                if vp8.memory.take_write_fault().is_some() {
+                    return Err(format!("${address:04X} is read-only"));
+                }
+            }
+
+            Ok(None)
+        }
+        "dis" | "d" => {
+            let start: usize = match argument(1) {
+                Some(location) => parse_location(location, view)?,
+                None => view.index.saturating_sub(DEFAULT_DISASSEMBLY_LENGTH / 4)
+            };
+            let length: usize = match argument(2) {
+                Some(length) => length.parse().map_err(|_| format!("Invalid length {length}"))?,
+                None => DEFAULT_DISASSEMBLY_LENGTH
+            };
+
+            for index in start..(start + length).min(view.instructions.len()) {
+                println!("{}", format_instruction(view.instructions, index, view.index));
+            }
+
+            Ok(None)
+        }
+        "labels" | "l" => {
+            for (name, index) in view.labels.iter().filter(|(name, _)| argument(1).map_or(true, |filter| name.contains(filter))) {
+                println!("{index:>5}  {name}");
+            }
+
+            Ok(None)
+        }
+        "break" | "b" | "tbreak" => {
+            let temporary: bool = parts[0] == "tbreak";
+            let (parts, condition): (&[&str], Option<Condition>) = split_condition(parts)?;
+            let index: usize = parse_stop_location(parts.get(1).ok_or(format!("{} needs a location", parts[0]))?, view)?;
+
+            let ignore_count: u64 = match parts.get(2..) {
+                Some(["after", count]) if !temporary => count.parse().map_err(|_| format!("Invalid hit count {count}"))?,
+                Some([]) | None => 0,
+
+                Some(_) => return Err(format!("Unexpected arguments for {}, type help for the commands", parts[0]))
+            };
+
+            let id: usize = breakpoints.add_breakpoint(index, condition, ignore_count, temporary);
+
+            println!("Breakpoint {id} at {index} {}", instruction_text(&view.instructions[index]));
+            Ok(None)
+        }
+        "watch" => {
+            let (parts, condition): (&[&str], Option<Condition>) = split_condition(parts)?;
+            let range: &str = parts.get(1).ok_or("watch needs an address range")?;
+            let (start, end): (u16, u16) = if range.contains('-') { config::parse_address_range(range)? } else { (config::parse_address(range)?, config::parse_address(range)?) };
+
+            let kind: WatchKind = match parts.get(2..) {
+                Some([kind]) => WatchKind::parse(kind)?,
+                Some([]) | None => WatchKind::Write,
+
+                Some(_) => return Err("Unexpected arguments for watch, type help for the commands".to_string())
+            };
+
+            println!("Watchpoint {}", breakpoints.add_watchpoint(start, end, kind, condition));
+            Ok(None)
+        }
+        "breaks" => {
+            for line in breakpoints.describe(view.instructions) {
+                println!("{line}");
+            }
+
+            Ok(None)
+        }
+        "delete" => {
+            match argument(1) {
+                Some(id) => {
+                    if !breakpoints.delete(id.parse().map_err(|_| format!("Invalid breakpoint number {id}"))?) {
+                        return Err(format!("No breakpoint or watchpoint {id}"));
+                    }
+                }
+                None => breakpoints.clear()
+            }
+
+            Ok(None)
+        }
+        "help" | "h" => {
+            println!("{HELP}");
+            Ok(None)
+        }
+        "quit" | "q" => Ok(Some(MonitorAction::Quit)),
+
+        command => Err(format!("Unknown command {command}, type help for the commands"))
+    }
+}
+
+// A location is the name of a label or the number of an instruction
+pub fn parse_location(location: &str, view: &DebugView) -> Result<usize, String> {
+    if let Some((_, index)) = view.labels.iter().find(|(name, _)| name == location) {
+        return Ok(*index);
+    }
+
+    match location.parse::<usize>() {
+        Ok(index) if index < view.instructions.len() => Ok(index),
+        _ => Err(format!("Unknown location {location}, expected a label or an instruction number"))
+    }
+}
+
+// Jumps land after a label, so stopping at a label stops at the first instruction after it
+fn parse_stop_location(location: &str, view: &DebugView) -> Result<usize, String> {
+    let mut index: usize = parse_location(location, view)?;
+
+    while view.instructions[index].opcode == Opcode::LABEL && index + 1 < view.instructions.len() {
+        index += 1;
+    }
+
+    Ok(index)
+}
+
+// Everything after an `if` is the condition
+fn split_condition<'a, 'b>(parts: &'a [&'b str]) -> Result<(&'a [&'b str], Option<Condition>), String> {
+    match parts.iter().position(|part| *part == "if") {
+        Some(position) => Ok((&parts[..position], Some(Condition::parse(&parts[position + 1..].join(" "))?))),
+        None => Ok((parts, None))
+    }
+}
+
+pub fn format_registers(view: &DebugView, vp8: &Vp8System) -> String {
+    let flag = |name: char, value: bool| if value { name } else { name.to_ascii_lowercase() };
+
+    format!(
+        "A=${:02X} X=${:02X} Y=${:02X} SP=${:02X} flags={}{}{}{}{}{} instruction={} cycles={} frame={}",
+        vp8.registers.get_acc(), vp8.registers.get_x(), vp8.registers.get_y(), vp8.registers.get_sp(),
+        flag('N', vp8.flags.get_negative_flag()), flag('V', vp8.flags.get_overflow_flag()), flag('D', vp8.flags.get_decimal_flag()),
+        flag('I', vp8.flags.get_interrupt_disable_flag()), flag('Z', vp8.flags.get_zerro_flag()), flag('C', vp8.flags.get_carry_flag()),
+        view.index, vp8.cycles, view.frame
+    )
+}
+
+// 16 bytes per line followed by the bytes as ASCII, with a dot for the bytes that are not printable
+pub fn format_memory_dump(vp8: &Vp8System, start: u16, length: usize) -> String {
+    let mut to_return: String = String::new();
+
+    for line_start in (0..length).step_by(16) {
+        let bytes: Vec<u8> = (line_start..(line_start + 16).min(length))
+            .map(|offset| vp8.memory.peek_mem_cell_value(start.wrapping_add(offset as u16) as usize))
+            .collect();
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+        let ascii: String = bytes.iter().map(|byte| if (0x20..0x7f).contains(byte) { *byte as char } else { '.' }).collect();
+
+        to_return += &format!("${:04X}  {:<47}  {ascii}\n", start.wrapping_add(line_start as u16), hex.join(" "));
+    }
+
+    to_return
+}
+
+// The program keeps the operand text of every instruction, so it is shown the way it was written
+pub fn format_instruction(instructions: &[Instruction], index: usize, current_index: usize) -> String {
+    let marker: &str = if index == current_index { "=>" } else { "  " };
+    let instruction: &Instruction = &instructions[index];
+
+    if instruction.opcode == Opcode::LABEL {
+        format!("{marker} {index:>5}  {}", instruction_text(instruction))
+    } else {
+        format!("{marker} {index:>5}      {}", instruction_text(instruction))
+    }
+}
+
+pub fn instruction_text(instruction: &Instruction) -> String {
+    if instruction.opcode == Opcode::LABEL {
+        format!("{}:", instruction.label_name)
+    } else {
+        format!("{:?} {}", instruction.opcode, instruction.label_name).trim_end().to_string()
+    }
+}
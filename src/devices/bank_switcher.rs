@@ -0,0 +1,88 @@
+use crate::bus::Device;
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+
+pub const BANK_SIZE: usize = 0x4000;
+
+// The banks are shared between the window that exposes the selected bank and the register that selects it
+pub struct BankedMemory {
+    banks: Vec<Vec<u8>>,
+    selected_bank: usize
+}
+
+impl BankedMemory {
+    pub fn init(bank_count: usize) -> BankedMemory {
+        BankedMemory {
+            banks: vec![vec![0; BANK_SIZE]; bank_count.max(1)],
+            selected_bank: 0
+        }
+    }
+
+    pub fn load_bank(&mut self, bank: usize, offset: usize, data: &[u8]) {
+        if bank >= self.banks.len() {
+            panic!("Bank {bank} does not exist, there are only {} banks", self.banks.len());
+        }
+
+        if offset + data.len() > BANK_SIZE {
+            panic!("Data at offset ${:04X} with length {} does not fit in bank {bank}", offset, data.len());
+        }
+
+        self.banks[bank][offset..offset + data.len()].copy_from_slice(data);
+    }
+}
+
+pub struct BankWindow {
+    banked_memory: Rc<RefCell<BankedMemory>>
+}
+
+impl BankWindow {
+    pub fn init(banked_memory: Rc<RefCell<BankedMemory>>) -> BankWindow {
+        BankWindow { banked_memory }
+    }
+}
+
+impl Device for BankWindow {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let mut banked_memory: RefMut<BankedMemory> = self.banked_memory.borrow_mut();
+        let selected_bank: usize = banked_memory.selected_bank;
+
+        banked_memory.banks[selected_bank][offset as usize] = value;
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let banked_memory: Ref<BankedMemory> = self.banked_memory.borrow();
+
+        banked_memory.banks[banked_memory.selected_bank][offset as usize]
+    }
+}
+
+// Writing a bank number swaps it into the window, numbers past the last bank wrap around
+pub struct BankSelect {
+    banked_memory: Rc<RefCell<BankedMemory>>
+}
+
+impl BankSelect {
+    pub fn init(banked_memory: Rc<RefCell<BankedMemory>>) -> BankSelect {
+        BankSelect { banked_memory }
+    }
+}
+
+impl Device for BankSelect {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, _offset: u16, value: u8) {
+        let mut banked_memory: RefMut<BankedMemory> = self.banked_memory.borrow_mut();
+
+        banked_memory.selected_bank = value as usize % banked_memory.banks.len();
+    }
+
+    fn peek(&self, _offset: u16) -> u8 {
+        self.banked_memory.borrow().selected_bank as u8
+    }
+}
@@ -0,0 +1,116 @@
+use crate::bus::Device;
+use crate::system::system;
+use std::cell::{Ref, RefCell};
+
+// Register layout, relative to the start of the blitter
+//  +0 command: writing starts a command, see the COMMAND_ constants
+//  +1/+2 source address (low byte, high byte)
+//  +3/+4 destination address (low byte, high byte)
+//  +5/+6 length in bytes, for rectangles the width of a row (low byte, high byte)
+//  +7 number of rows of a rectangle
+//  +8/+9 source stride, the distance between the starts of two rows (low byte, high byte)
+//  +A/+B destination stride (low byte, high byte)
+//  +C fill value
+const COMMAND: u16 = 0;
+const SOURCE_LOW: u16 = 1;
+const DESTINATION_LOW: u16 = 3;
+const LENGTH_LOW: u16 = 5;
+const ROWS: u16 = 7;
+const SOURCE_STRIDE_LOW: u16 = 8;
+const DESTINATION_STRIDE_LOW: u16 = 0xa;
+const FILL_VALUE: u16 = 0xc;
+
+pub const COMMAND_COPY: u8 = 1;
+pub const COMMAND_FILL: u8 = 2;
+pub const COMMAND_COPY_RECTANGLE: u8 = 3;
+pub const COMMAND_FILL_RECTANGLE: u8 = 4;
+
+// Copies and fills blocks of memory for the CPU. The device only latches the command, the emulator runs it after the instruction
+// that wrote it with run_pending_command and stalls the CPU for the configured number of cycles per byte
+pub struct Blitter {
+    registers: [u8; 13],
+    pending_command: Option<u8>,
+    cycles_per_byte: u32
+}
+
+impl Blitter {
+    pub fn init(cycles_per_byte: u32) -> Blitter {
+        Blitter {
+            registers: [0; 13],
+            pending_command: None,
+            cycles_per_byte
+        }
+    }
+
+    fn register_pair(&self, low_offset: u16) -> u16 {
+        u16::from_le_bytes([self.registers[low_offset as usize], self.registers[low_offset as usize + 1]])
+    }
+}
+
+impl Device for Blitter {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if let Some(register) = self.registers.get_mut(offset as usize) {
+            *register = value;
+        }
+
+        if offset == COMMAND {
+            self.pending_command = Some(value);
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.registers.get(offset as usize).copied().unwrap_or(0)
+    }
+}
+
+// Runs the command the program has written, if any, and returns the number of cycles the CPU is stalled for.
+// Bytes are copied one at a time from the lowest address up, so an overlapping copy to a higher address repeats the source
+pub fn run_pending_command(blitter: &RefCell<Blitter>, memory: &mut system::Memory) -> u32 {
+    let command: u8 = match blitter.borrow_mut().pending_command.take() {
+        Some(command) => command,
+        None => return 0
+    };
+
+    let (source, destination, length, rows, source_stride, destination_stride, fill_value, cycles_per_byte) = {
+        let blitter: Ref<Blitter> = blitter.borrow();
+
+        (
+            blitter.register_pair(SOURCE_LOW),
+            blitter.register_pair(DESTINATION_LOW),
+            blitter.register_pair(LENGTH_LOW),
+            blitter.registers[ROWS as usize] as u16,
+            blitter.register_pair(SOURCE_STRIDE_LOW),
+            blitter.register_pair(DESTINATION_STRIDE_LOW),
+            blitter.registers[FILL_VALUE as usize],
+            blitter.cycles_per_byte
+        )
+    };
+
+    let rows: u16 = match command {
+        COMMAND_COPY | COMMAND_FILL => 1,
+        COMMAND_COPY_RECTANGLE | COMMAND_FILL_RECTANGLE => rows,
+
+        _ => return 0
+    };
+
+    for row in 0..rows {
+        let source_row: u16 = source.wrapping_add(row.wrapping_mul(source_stride));
+        let destination_row: u16 = destination.wrapping_add(row.wrapping_mul(destination_stride));
+
+        for offset in 0..length {
+            let value: u8 = match command {
+                COMMAND_COPY | COMMAND_COPY_RECTANGLE => memory.get_mem_cell_value(source_row.wrapping_add(offset) as usize),
+                _ => fill_value
+            };
+
+            memory.set_mem_cell_value(destination_row.wrapping_add(offset) as usize, value);
+        }
+    }
+
+    // Multiplied in u64 so a large rectangle saturates the stall instead of overflowing it
+    (rows as u64 * length as u64 * cycles_per_byte as u64).min(u32::MAX as u64) as u32
+}
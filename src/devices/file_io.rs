@@ -0,0 +1,283 @@
+use crate::bus::Device;
+use crate::system::system;
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+// Register layout, relative to the start of the file device
+//  +0 command: writing starts a command, see the COMMAND_ constants
+//  +1 status: result of the last command, see the STATUS_ constants
+//  +2/+3 address of the zero terminated file name (low byte, high byte)
+//  +4/+5 address of the buffer the data is copied from or to (low byte, high byte)
+//  +6/+7 number of bytes to read or write (low byte, high byte)
+//  +8/+9 number of bytes the last read or write copied (low byte, high byte)
+const COMMAND: u16 = 0;
+const STATUS: u16 = 1;
+const NAME_ADDRESS_LOW: u16 = 2;
+const BUFFER_ADDRESS_LOW: u16 = 4;
+const LENGTH_LOW: u16 = 6;
+const LENGTH_HIGH: u16 = 7;
+const BYTES_TRANSFERRED_LOW: u16 = 8;
+const BYTES_TRANSFERRED_HIGH: u16 = 9;
+
+pub const COMMAND_OPEN_READ: u8 = 1;
+pub const COMMAND_OPEN_WRITE: u8 = 2;
+pub const COMMAND_OPEN_APPEND: u8 = 3;
+pub const COMMAND_READ: u8 = 4;
+pub const COMMAND_WRITE: u8 = 5;
+pub const COMMAND_CLOSE: u8 = 6;
+
+pub const STATUS_OK: u8 = 0;
+pub const STATUS_NO_SANDBOX: u8 = 1;
+pub const STATUS_INVALID_PATH: u8 = 2;
+pub const STATUS_NOT_FOUND: u8 = 3;
+pub const STATUS_NOT_OPEN: u8 = 4;
+pub const STATUS_IO_ERROR: u8 = 5;
+pub const STATUS_END_OF_FILE: u8 = 6;
+pub const STATUS_UNKNOWN_COMMAND: u8 = 7;
+
+// File names longer than this are rejected before they are looked up
+const MAX_NAME_LENGTH: usize = 255;
+
+// Gives programs access to the files of one host directory. The device only latches the command,
+// the emulator runs it after the instruction that wrote it with run_pending_command, which copies the data to and from memory
+pub struct FileIo {
+    sandbox: Option<PathBuf>,
+    registers: [u8; 10],
+    pending_command: Option<u8>,
+    open_file: Option<File>
+}
+
+impl FileIo {
+    pub fn init(sandbox: Option<String>) -> FileIo {
+        let sandbox: Option<PathBuf> = sandbox.map(|path| {
+            fs::canonicalize(&path).unwrap_or_else(|_| panic!("The file sandbox {path} does not exist"))
+        });
+
+        FileIo {
+            sandbox,
+            registers: [0; 10],
+            pending_command: None,
+            open_file: None
+        }
+    }
+
+    fn register_pair(&self, low_offset: u16) -> u16 {
+        u16::from_le_bytes([self.registers[low_offset as usize], self.registers[low_offset as usize + 1]])
+    }
+
+    fn finish(&mut self, status: u8, bytes_transferred: u16) {
+        self.registers[STATUS as usize] = status;
+        let transferred_offset: usize = BYTES_TRANSFERRED_LOW as usize;
+
+        self.registers[transferred_offset..transferred_offset + 2].copy_from_slice(&bytes_transferred.to_le_bytes());
+    }
+
+    fn open(&mut self, name: &str, command: u8) -> u8 {
+        self.open_file = None;
+
+        let path: PathBuf = match self.sandboxed_path(name) {
+            Ok(path) => path,
+            Err(status) => return status
+        };
+
+        let result: std::io::Result<File> = match command {
+            COMMAND_OPEN_READ => File::open(&path),
+            COMMAND_OPEN_WRITE => File::create(&path),
+            _ => OpenOptions::new().append(true).create(true).open(&path)
+        };
+
+        match result {
+            Ok(file) => {
+                self.open_file = Some(file);
+                STATUS_OK
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => STATUS_NOT_FOUND,
+            Err(_) => STATUS_IO_ERROR
+        }
+    }
+
+    // Only plain relative names are accepted, and symbolic links may not lead out of the sandbox either
+    fn sandboxed_path(&self, name: &str) -> Result<PathBuf, u8> {
+        let sandbox: &PathBuf = self.sandbox.as_ref().ok_or(STATUS_NO_SANDBOX)?;
+        let relative_path: &Path = Path::new(name);
+
+        if name.is_empty() || !relative_path.components().all(|component| matches!(component, Component::Normal(_))) {
+            return Err(STATUS_INVALID_PATH);
+        }
+
+        let path: PathBuf = sandbox.join(relative_path);
+        let existing_path: PathBuf = match fs::canonicalize(&path) {
+            Ok(canonical_path) => canonical_path,
+            // A link that leads nowhere could be created through, wherever it points
+            Err(_) if fs::symlink_metadata(&path).is_ok() => return Err(STATUS_INVALID_PATH),
+            Err(_) => path.parent().and_then(|parent| fs::canonicalize(parent).ok()).ok_or(STATUS_NOT_FOUND)?
+        };
+
+        if existing_path.starts_with(sandbox) {
+            Ok(path)
+        } else {
+            Err(STATUS_INVALID_PATH)
+        }
+    }
+}
+
+impl Device for FileIo {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            COMMAND => {
+                self.registers[COMMAND as usize] = value;
+                self.pending_command = Some(value);
+            }
+            NAME_ADDRESS_LOW..=LENGTH_HIGH => self.registers[offset as usize] = value,
+
+            _ => { }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            COMMAND..=BYTES_TRANSFERRED_HIGH => self.registers[offset as usize],
+
+            _ => 0
+        }
+    }
+}
+
+// Runs the command the program has written, if any. The device is borrowed only around its own work
+// so the copied data may go anywhere in memory, even over the device registers
+pub fn run_pending_command(file_io: &RefCell<FileIo>, memory: &mut system::Memory) {
+    let command: u8 = match file_io.borrow_mut().pending_command.take() {
+        Some(command) => command,
+        None => return
+    };
+
+    let name_address: u16 = file_io.borrow().register_pair(NAME_ADDRESS_LOW);
+    let buffer_address: u16 = file_io.borrow().register_pair(BUFFER_ADDRESS_LOW);
+    let length: u16 = file_io.borrow().register_pair(LENGTH_LOW);
+
+    match command {
+        COMMAND_OPEN_READ | COMMAND_OPEN_WRITE | COMMAND_OPEN_APPEND => {
+            let status: u8 = match read_file_name(name_address, memory) {
+                Some(name) => file_io.borrow_mut().open(&name, command),
+                None => STATUS_INVALID_PATH
+            };
+
+            file_io.borrow_mut().finish(status, 0);
+        }
+        COMMAND_READ => {
+            let mut bytes: Vec<u8> = vec![0; length as usize];
+            let result: Option<std::io::Result<usize>> = file_io.borrow_mut().open_file.as_mut().map(|file| read_block(file, &mut bytes));
+
+            let (status, count): (u8, usize) = match result {
+                None => (STATUS_NOT_OPEN, 0),
+                Some(Err(_)) => (STATUS_IO_ERROR, 0),
+                Some(Ok(count)) if count < bytes.len() => (STATUS_END_OF_FILE, count),
+                Some(Ok(count)) => (STATUS_OK, count)
+            };
+
+            for (offset, byte) in bytes.iter().take(count).enumerate() {
+                memory.set_mem_cell_value(buffer_address.wrapping_add(offset as u16) as usize, *byte);
+            }
+
+            file_io.borrow_mut().finish(status, count as u16);
+        }
+        COMMAND_WRITE => {
+            let bytes: Vec<u8> = (0..length).map(|offset| memory.get_mem_cell_value(buffer_address.wrapping_add(offset) as usize)).collect();
+            let result: Option<std::io::Result<()>> = file_io.borrow_mut().open_file.as_mut().map(|file| file.write_all(&bytes));
+
+            match result {
+                None => file_io.borrow_mut().finish(STATUS_NOT_OPEN, 0),
+                Some(Err(_)) => file_io.borrow_mut().finish(STATUS_IO_ERROR, 0),
+                Some(Ok(())) => file_io.borrow_mut().finish(STATUS_OK, length)
+            }
+        }
+        COMMAND_CLOSE => {
+            let was_open: bool = file_io.borrow_mut().open_file.take().is_some();
+
+            file_io.borrow_mut().finish(if was_open { STATUS_OK } else { STATUS_NOT_OPEN }, 0);
+        }
+
+        _ => file_io.borrow_mut().finish(STATUS_UNKNOWN_COMMAND, 0)
+    }
+}
+
+fn read_file_name(address: u16, memory: &mut system::Memory) -> Option<String> {
+    let mut name: Vec<u8> = vec![];
+
+    loop {
+        let byte: u8 = memory.get_mem_cell_value(address.wrapping_add(name.len() as u16) as usize);
+
+        if byte == 0 {
+            return String::from_utf8(name).ok();
+        }
+        if name.len() == MAX_NAME_LENGTH {
+            return None;
+        }
+
+        name.push(byte);
+    }
+}
+
+// Keeps reading until the buffer is full or the file ends, a single read may return less than was asked for
+fn read_block(file: &mut File, bytes: &mut [u8]) -> std::io::Result<usize> {
+    let mut count: usize = 0;
+
+    while count < bytes.len() {
+        match file.read(&mut bytes[count..])? {
+            0 => break,
+            read => count += read
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    // A fresh sandbox with a directory next to it that the program must not reach
+    fn create_sandbox(name: &str) -> (PathBuf, PathBuf) {
+        let root: PathBuf = std::env::temp_dir().join(format!("vp8-file-io-{}-{name}", std::process::id()));
+        let sandbox: PathBuf = root.join("sandbox");
+        let outside: PathBuf = root.join("outside");
+
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&sandbox).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+
+        (sandbox, outside)
+    }
+
+    #[test]
+    fn files_inside_the_sandbox_are_accepted() {
+        let (sandbox, _) = create_sandbox("inside");
+        let file_io: FileIo = FileIo::init(Some(sandbox.to_string_lossy().to_string()));
+
+        assert!(file_io.sandboxed_path("new.txt").is_ok());
+        assert_eq!(file_io.sandboxed_path("../outside/new.txt"), Err(STATUS_INVALID_PATH));
+    }
+
+    #[test]
+    fn links_leading_out_of_the_sandbox_are_rejected() {
+        let (sandbox, outside) = create_sandbox("links");
+
+        fs::write(outside.join("secret.txt"), b"secret").unwrap();
+        symlink(outside.join("secret.txt"), sandbox.join("existing")).unwrap();
+        symlink(outside.join("missing.txt"), sandbox.join("dangling")).unwrap();
+
+        let mut file_io: FileIo = FileIo::init(Some(sandbox.to_string_lossy().to_string()));
+
+        assert_eq!(file_io.sandboxed_path("existing"), Err(STATUS_INVALID_PATH));
+        assert_eq!(file_io.sandboxed_path("dangling"), Err(STATUS_INVALID_PATH));
+        assert_eq!(file_io.open("dangling", COMMAND_OPEN_WRITE), STATUS_INVALID_PATH);
+        assert!(!outside.join("missing.txt").exists());
+    }
+}
@@ -0,0 +1,49 @@
+use crate::bus::Device;
+use crate::devices::keyboard::{KEY_DOWN, KEY_ENTER, KEY_LEFT, KEY_RIGHT, KEY_SPACE, KEY_UP};
+
+// Bit order of the joystick register, the same order as the NES controller
+pub const BUTTON_NAMES: [&str; 8] = ["a", "b", "select", "start", "up", "down", "left", "right"];
+pub const DEFAULT_BINDINGS: [u8; 8] = [b'Z', b'X', KEY_SPACE, KEY_ENTER, KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT];
+
+pub struct Joystick {
+    buttons: u8
+}
+
+impl Joystick {
+    pub fn init() -> Joystick {
+        Joystick {
+            buttons: 0
+        }
+    }
+
+    pub fn press(&mut self, button: u8) {
+        self.buttons |= button;
+    }
+
+    pub fn release(&mut self, button: u8) {
+        self.buttons &= !button;
+    }
+}
+
+impl Device for Joystick {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) { }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            0 => self.buttons,
+            _ => 0
+        }
+    }
+}
+
+pub fn button_from_name(name: &str) -> Option<u8> {
+    BUTTON_NAMES.iter().position(|button_name| *button_name == name.to_lowercase()).map(|bit| 1 << bit)
+}
+
+pub fn button_name(button: u8) -> &'static str {
+    BUTTON_NAMES[button.trailing_zeros() as usize % 8]
+}
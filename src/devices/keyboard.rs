@@ -0,0 +1,115 @@
+use crate::bus::Device;
+
+pub const KEY_ENTER: u8 = 0x0d;
+pub const KEY_SPACE: u8 = 0x20;
+pub const KEY_UP: u8 = 0x80;
+pub const KEY_DOWN: u8 = 0x81;
+pub const KEY_LEFT: u8 = 0x82;
+pub const KEY_RIGHT: u8 = 0x83;
+
+// Register layout, relative to the start of the keyboard
+//  +0 last key code, reading it acknowledges the key
+//  +1 held keys: up, down, left, right, W, A, S, D from bit 0 to bit 7
+//  +2 held keys: space (bit 0), enter (bit 1)
+//  +3 status: bit 0 is set while the last key has not been read
+//  +4 control: bit 0 enables an IRQ on every key press
+const LAST_KEY: u16 = 0;
+const HELD_KEYS_LOW: u16 = 1;
+const HELD_KEYS_HIGH: u16 = 2;
+const STATUS: u16 = 3;
+const CONTROL: u16 = 4;
+
+const HELD_KEYS: [u8; 10] = [KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT, b'W', b'A', b'S', b'D', KEY_SPACE, KEY_ENTER];
+
+pub struct Keyboard {
+    last_key: u8,
+    held_keys: u16,
+    key_available: bool,
+    control: u8
+}
+
+impl Keyboard {
+    pub fn init() -> Keyboard {
+        Keyboard {
+            last_key: 0,
+            held_keys: 0,
+            key_available: false,
+            control: 0
+        }
+    }
+
+    pub fn press(&mut self, key_code: u8) {
+        self.last_key = key_code;
+        self.key_available = true;
+
+        if let Some(bit) = HELD_KEYS.iter().position(|key| *key == key_code) {
+            self.held_keys |= 1 << bit;
+        }
+    }
+
+    pub fn release(&mut self, key_code: u8) {
+        if let Some(bit) = HELD_KEYS.iter().position(|key| *key == key_code) {
+            self.held_keys &= !(1 << bit);
+        }
+    }
+}
+
+impl Device for Keyboard {
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == LAST_KEY {
+            self.key_available = false;
+        }
+
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == CONTROL {
+            self.control = value;
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            LAST_KEY => self.last_key,
+            HELD_KEYS_LOW => self.held_keys as u8,
+            HELD_KEYS_HIGH => (self.held_keys >> 8) as u8,
+            STATUS => self.key_available as u8,
+            CONTROL => self.control,
+
+            _ => 0
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.control & 1 != 0 && self.key_available
+    }
+}
+
+// Names used by input scripts: up, down, left, right, space, enter or a single letter or digit
+pub fn key_code_from_name(name: &str) -> Option<u8> {
+    match name.to_lowercase().as_str() {
+        "up" => Some(KEY_UP),
+        "down" => Some(KEY_DOWN),
+        "left" => Some(KEY_LEFT),
+        "right" => Some(KEY_RIGHT),
+        "space" => Some(KEY_SPACE),
+        "enter" => Some(KEY_ENTER),
+
+        single if single.len() == 1 && single.chars().all(|c| c.is_ascii_alphanumeric()) => Some(single.to_ascii_uppercase().as_bytes()[0]),
+        _ => None
+    }
+}
+
+pub fn key_name(key_code: u8) -> String {
+    match key_code {
+        KEY_UP => "up".to_string(),
+        KEY_DOWN => "down".to_string(),
+        KEY_LEFT => "left".to_string(),
+        KEY_RIGHT => "right".to_string(),
+        KEY_SPACE => "space".to_string(),
+        KEY_ENTER => "enter".to_string(),
+
+        _ => (key_code as char).to_ascii_lowercase().to_string()
+    }
+}
@@ -0,0 +1,15 @@
+pub mod ram;
+pub mod rom;
+pub mod random_number_generator;
+pub mod bank_switcher;
+pub mod keyboard;
+pub mod mouse;
+pub mod joystick;
+pub mod sound;
+pub mod timer;
+pub mod video;
+pub mod serial;
+pub mod file_io;
+pub mod palette;
+pub mod sprites;
+pub mod blitter;
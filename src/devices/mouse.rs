@@ -0,0 +1,104 @@
+use crate::bus::Device;
+
+pub const BUTTON_LEFT: u8 = 0b001;
+pub const BUTTON_RIGHT: u8 = 0b010;
+pub const BUTTON_MIDDLE: u8 = 0b100;
+
+// Register layout, relative to the start of the mouse
+//  +0 pointer X in screen pixels
+//  +1 pointer Y in screen pixels
+//  +2 held buttons: left (bit 0), right (bit 1), middle (bit 2)
+//  +3 click status: bit 0 is set after a button press until this register is read
+//  +4 control: bit 0 enables an IRQ on every button press
+const POINTER_X: u16 = 0;
+const POINTER_Y: u16 = 1;
+const BUTTONS: u16 = 2;
+const CLICK_STATUS: u16 = 3;
+const CONTROL: u16 = 4;
+
+pub struct Mouse {
+    x: u8,
+    y: u8,
+    buttons: u8,
+    clicked: bool,
+    control: u8
+}
+
+impl Mouse {
+    pub fn init() -> Mouse {
+        Mouse {
+            x: 0,
+            y: 0,
+            buttons: 0,
+            clicked: false,
+            control: 0
+        }
+    }
+
+    pub fn move_to(&mut self, x: u8, y: u8) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn press(&mut self, button: u8) {
+        self.buttons |= button;
+        self.clicked = true;
+    }
+
+    pub fn release(&mut self, button: u8) {
+        self.buttons &= !button;
+    }
+}
+
+impl Device for Mouse {
+    fn read(&mut self, offset: u16) -> u8 {
+        let value: u8 = self.peek(offset);
+
+        if offset == CLICK_STATUS {
+            self.clicked = false;
+        }
+
+        value
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == CONTROL {
+            self.control = value;
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            POINTER_X => self.x,
+            POINTER_Y => self.y,
+            BUTTONS => self.buttons,
+            CLICK_STATUS => self.clicked as u8,
+            CONTROL => self.control,
+
+            _ => 0
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.control & 1 != 0 && self.clicked
+    }
+}
+
+pub fn button_from_name(name: &str) -> Option<u8> {
+    match name.to_lowercase().as_str() {
+        "left" => Some(BUTTON_LEFT),
+        "right" => Some(BUTTON_RIGHT),
+        "middle" => Some(BUTTON_MIDDLE),
+
+        _ => None
+    }
+}
+
+pub fn button_name(button: u8) -> &'static str {
+    match button {
+        BUTTON_RIGHT => "right",
+        BUTTON_MIDDLE => "middle",
+
+        _ => "left"
+    }
+}
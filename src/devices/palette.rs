@@ -0,0 +1,61 @@
+use crate::bus::Device;
+
+pub const PALETTE_SIZE: usize = 256;
+
+// The original 16 colours of the screen, they start the default palette so old programs look the same
+const DEFAULT_COLOURS: [[u8; 3]; 16] = [
+    [0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 255, 255],
+    [128, 0, 128], [0, 255, 0], [0, 0, 255], [255, 255, 0],
+    [255, 165, 0], [165, 42, 42], [255, 204, 204], [105, 105, 105],
+    [128, 128, 128], [0, 128, 0], [0, 0, 128], [64, 64, 64]
+];
+
+// Palette RAM, three bytes (red, green, blue) per colour index, so colour N starts at offset N * 3
+pub struct Palette {
+    colours: [[u8; 3]; PALETTE_SIZE]
+}
+
+impl Palette {
+    // After the 16 original colours come a 6x6x6 colour cube and a grey ramp, like the 256 colours of xterm
+    pub fn init() -> Palette {
+        let mut colours: [[u8; 3]; PALETTE_SIZE] = [[0; 3]; PALETTE_SIZE];
+        let cube_levels: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+        colours[..16].copy_from_slice(&DEFAULT_COLOURS);
+
+        for index in 0..216 {
+            colours[16 + index] = [cube_levels[index / 36], cube_levels[index / 6 % 6], cube_levels[index % 6]];
+        }
+
+        for index in 0..24 {
+            let level: u8 = 8 + index as u8 * 10;
+
+            colours[232 + index] = [level; 3];
+        }
+
+        Palette { colours }
+    }
+
+    pub fn colour(&self, index: u8) -> [u8; 3] {
+        self.colours[index as usize]
+    }
+}
+
+impl Device for Palette {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if let Some(colour) = self.colours.get_mut(offset as usize / 3) {
+            colour[offset as usize % 3] = value;
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match self.colours.get(offset as usize / 3) {
+            Some(colour) => colour[offset as usize % 3],
+            None => 0
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::bus::Device;
+
+pub struct Ram {
+    cells: Vec<u8>
+}
+
+impl Ram {
+    pub fn init(size: usize) -> Ram {
+        Ram {
+            cells: vec![0; size]
+        }
+    }
+
+    pub fn init_with_contents(cells: Vec<u8>) -> Ram {
+        Ram { cells }
+    }
+}
+
+impl Device for Ram {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.cells[offset as usize]
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.cells[offset as usize] = value;
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.cells[offset as usize]
+    }
+}
@@ -0,0 +1,31 @@
+use crate::bus::Device;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+// Every read produces a new random byte, writes are ignored. The generator is seeded so recorded runs can be replayed exactly
+pub struct RandomNumberGenerator {
+    generator: StdRng,
+    last_value: u8
+}
+
+impl RandomNumberGenerator {
+    pub fn init(seed: u64) -> RandomNumberGenerator {
+        RandomNumberGenerator {
+            generator: StdRng::seed_from_u64(seed),
+            last_value: 0
+        }
+    }
+}
+
+impl Device for RandomNumberGenerator {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.last_value = self.generator.gen();
+        self.last_value
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) { }
+
+    fn peek(&self, _offset: u16) -> u8 {
+        self.last_value
+    }
+}
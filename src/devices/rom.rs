@@ -0,0 +1,42 @@
+use crate::bus::Device;
+use crate::config::RomWriteMode;
+
+// Read-only memory holding what the loader placed at its addresses before it was protected.
+// Writes are dropped, and with the trap write mode the address of the last one is kept for the emulator to stop on
+pub struct Rom {
+    start: u16,
+    cells: Vec<u8>,
+    write_mode: RomWriteMode,
+    write_fault: Option<u16>
+}
+
+impl Rom {
+    pub fn init(start: u16, cells: Vec<u8>, write_mode: RomWriteMode) -> Rom {
+        Rom {
+            start,
+            cells,
+            write_mode,
+            write_fault: None
+        }
+    }
+
+    pub fn take_write_fault(&mut self) -> Option<u16> {
+        self.write_fault.take()
+    }
+}
+
+impl Device for Rom {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.cells[offset as usize]
+    }
+
+    fn write(&mut self, offset: u16, _value: u8) {
+        if self.write_mode == RomWriteMode::Trap {
+            self.write_fault = Some(self.start + offset);
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.cells[offset as usize]
+    }
+}
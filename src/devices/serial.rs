@@ -0,0 +1,130 @@
+use crate::bus::Device;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// Register layout, relative to the start of the serial port
+//  +0 data: writing sends a byte to the output, reading takes the next received byte
+//  +1 status: bit 0 is set while a received byte is waiting, bit 1 once the input has ended
+//  +2 control: bit 0 enables an IRQ while a received byte is waiting
+const DATA: u16 = 0;
+const STATUS: u16 = 1;
+const CONTROL: u16 = 2;
+
+const BYTE_AVAILABLE: u8 = 0b01;
+const INPUT_ENDED: u8 = 0b10;
+
+pub struct Serial {
+    output: Box<dyn Write>,
+    flush_every_byte: bool,
+    received: VecDeque<u8>,
+    input_ended: bool,
+    control: u8
+}
+
+impl Serial {
+    // Writes to stdout unless an output file is given. A file is read up front, which keeps headless runs deterministic,
+    // otherwise the input arrives through receive as input events and without any it is empty and already ended
+    pub fn init(output_path: Option<String>, input_path: Option<String>, receives_input_events: bool) -> Serial {
+        let (output, flush_every_byte): (Box<dyn Write>, bool) = match output_path {
+            Some(path) => (Box::new(BufWriter::new(File::create(path).expect("Error creating serial output file"))), false),
+            None => (Box::new(io::stdout()), true)
+        };
+
+        let (received, input_ended): (VecDeque<u8>, bool) = match input_path {
+            Some(path) => (std::fs::read(path).expect("Error reading serial input file").into(), true),
+            None => (VecDeque::new(), !receives_input_events)
+        };
+
+        Serial {
+            output,
+            flush_every_byte,
+            received,
+            input_ended,
+            control: 0
+        }
+    }
+
+    pub fn receive(&mut self, byte: u8) {
+        self.received.push_back(byte);
+    }
+
+    pub fn end_input(&mut self) {
+        self.input_ended = true;
+    }
+}
+
+impl Device for Serial {
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == DATA {
+            return self.received.pop_front().unwrap_or(0);
+        }
+
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            DATA => {
+                self.output.write_all(&[value]).expect("Error writing serial output");
+
+                if self.flush_every_byte {
+                    let _ = self.output.flush();
+                }
+            }
+            CONTROL => self.control = value,
+
+            _ => { }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            DATA => self.received.front().copied().unwrap_or(0),
+            STATUS => {
+                let mut status: u8 = 0;
+
+                if !self.received.is_empty() {
+                    status |= BYTE_AVAILABLE;
+                }
+                if self.input_ended && self.received.is_empty() {
+                    status |= INPUT_ENDED;
+                }
+
+                status
+            }
+            CONTROL => self.control,
+
+            _ => 0
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.control & 1 != 0 && !self.received.is_empty()
+    }
+}
+
+impl Drop for Serial {
+    fn drop(&mut self) {
+        let _ = self.output.flush();
+    }
+}
+
+// Stdin is read on its own thread so a program polling the port never blocks the emulator,
+// the channel disconnects once stdin has ended
+pub fn spawn_stdin_reader() -> Receiver<u8> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            match byte {
+                Ok(byte) if sender.send(byte).is_ok() => { }
+                _ => break
+            }
+        }
+    });
+
+    receiver
+}
@@ -0,0 +1,198 @@
+use crate::bus::Device;
+use crate::system::system::CPU_CLOCK_HZ;
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+const CHANNEL_AMPLITUDE: i32 = 512;
+const DUTY_CYCLES: [f64; 4] = [0.125, 0.25, 0.5, 0.75];
+
+// Register layout, relative to the start of the sound device
+//  +0 - +3 square channel 1: frequency in Hz (low, high), volume (0 - 15), duty cycle (0 - 3 for 12.5%, 25%, 50%, 75%)
+//  +4 - +7 square channel 2, same layout as channel 1
+//  +8 - +A noise channel: frequency in Hz (low, high), volume (0 - 15)
+//  +F control: bit 0 enables square channel 1, bit 1 square channel 2, bit 2 the noise channel
+const SQUARE_1: u16 = 0x0;
+const SQUARE_2: u16 = 0x4;
+const NOISE: u16 = 0x8;
+const CONTROL: u16 = 0xf;
+
+pub struct SoundGenerator {
+    registers: [u8; 16],
+    square_phases: [f64; 2],
+    noise_phase: f64,
+    noise_shift_register: u16,
+    cycles_until_sample: f64,
+    samples: Vec<i16>
+}
+
+impl SoundGenerator {
+    pub fn init() -> SoundGenerator {
+        SoundGenerator {
+            registers: [0; 16],
+            square_phases: [0.0; 2],
+            noise_phase: 0.0,
+            noise_shift_register: 1,
+            cycles_until_sample: 0.0,
+            samples: vec![]
+        }
+    }
+
+    // Hands over the samples generated since the last call, they are taken once per frame so every one reaches the wav file
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        std::mem::take(&mut self.samples)
+    }
+
+    fn frequency(&self, channel_start: u16) -> f64 {
+        (self.registers[channel_start as usize] as u16 | (self.registers[channel_start as usize + 1] as u16) << 8) as f64
+    }
+
+    fn volume(&self, channel_start: u16) -> i32 {
+        (self.registers[channel_start as usize + 2] & 0x0f) as i32
+    }
+
+    fn generate_sample(&mut self) -> i16 {
+        let control: u8 = self.registers[CONTROL as usize];
+        let mut sample: i32 = 0;
+
+        for (channel, channel_start) in [SQUARE_1, SQUARE_2].into_iter().enumerate() {
+            let duty_cycle: f64 = DUTY_CYCLES[(self.registers[channel_start as usize + 3] & 0b11) as usize];
+
+            self.square_phases[channel] = (self.square_phases[channel] + self.frequency(channel_start) / SAMPLE_RATE as f64).fract();
+
+            if control & (1 << channel) != 0 {
+                let level: i32 = if self.square_phases[channel] < duty_cycle { 1 } else { -1 };
+                sample += level * self.volume(channel_start) * CHANNEL_AMPLITUDE;
+            }
+        }
+
+        // The noise comes from a 15 bit linear feedback shift register clocked at the noise frequency
+        self.noise_phase += self.frequency(NOISE) / SAMPLE_RATE as f64;
+
+        while self.noise_phase >= 1.0 {
+            let feedback: u16 = (self.noise_shift_register ^ (self.noise_shift_register >> 1)) & 1;
+
+            self.noise_shift_register = (self.noise_shift_register >> 1) | (feedback << 14);
+            self.noise_phase -= 1.0;
+        }
+
+        if control & 0b100 != 0 {
+            let level: i32 = if self.noise_shift_register & 1 != 0 { 1 } else { -1 };
+            sample += level * self.volume(NOISE) * CHANNEL_AMPLITUDE;
+        }
+
+        sample as i16
+    }
+}
+
+impl Device for SoundGenerator {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.registers[offset as usize] = value;
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.registers[offset as usize]
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles_until_sample -= cycles as f64;
+
+        while self.cycles_until_sample <= 0.0 {
+            let sample: i16 = self.generate_sample();
+
+            self.samples.push(sample);
+            self.cycles_until_sample += CPU_CLOCK_HZ as f64 / SAMPLE_RATE as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sets a channel to the given frequency in Hz and volume, with the duty cycle register for square channels
+    fn set_channel(sound: &mut SoundGenerator, channel_start: u16, frequency: u16, volume: u8, duty_cycle: u8) {
+        sound.write(channel_start, frequency as u8);
+        sound.write(channel_start + 1, (frequency >> 8) as u8);
+        sound.write(channel_start + 2, volume);
+
+        if channel_start != NOISE {
+            sound.write(channel_start + 3, duty_cycle);
+        }
+    }
+
+    // Runs the sound generator until it has produced the given number of samples
+    fn generate(sound: &mut SoundGenerator, count: usize) -> Vec<i16> {
+        let mut samples: Vec<i16> = vec![];
+
+        while samples.len() < count {
+            sound.tick(1);
+            samples.extend(sound.take_samples());
+        }
+
+        samples.truncate(count);
+        samples
+    }
+
+    #[test]
+    fn one_second_of_cycles_produces_one_second_of_samples() {
+        let mut sound: SoundGenerator = SoundGenerator::init();
+
+        for _ in 0..CPU_CLOCK_HZ / 1000 {
+            sound.tick(1000);
+        }
+
+        // The first sample is taken on the first cycle, then one every CPU_CLOCK_HZ / SAMPLE_RATE cycles up to and including the last
+        assert_eq!(sound.take_samples().len(), SAMPLE_RATE as usize + 1);
+        assert!(sound.take_samples().is_empty());
+    }
+
+    #[test]
+    fn channels_are_silent_until_enabled() {
+        let mut sound: SoundGenerator = SoundGenerator::init();
+
+        set_channel(&mut sound, SQUARE_1, 441, 15, 2);
+        set_channel(&mut sound, NOISE, 1000, 15, 0);
+
+        assert!(generate(&mut sound, 100).iter().all(|&sample| sample == 0));
+
+        sound.write(CONTROL, 0b001);
+
+        assert!(generate(&mut sound, 100).iter().all(|&sample| sample.abs() == 15 * CHANNEL_AMPLITUDE as i16));
+    }
+
+    #[test]
+    fn duty_cycle_sets_the_share_of_high_samples() {
+        // The sample at the start of the next period is high as well, so 12.5% rounds up to 13 samples
+        for (duty_cycle, high_samples) in [(0, 13), (1, 25), (2, 50), (3, 75)] {
+            let mut sound: SoundGenerator = SoundGenerator::init();
+
+            // 441 Hz is exactly 100 samples per period
+            set_channel(&mut sound, SQUARE_2, 441, 8, duty_cycle);
+            sound.write(CONTROL, 0b010);
+
+            let samples: Vec<i16> = generate(&mut sound, 100);
+
+            assert_eq!(samples.iter().filter(|&&sample| sample == 8 * CHANNEL_AMPLITUDE as i16).count(), high_samples);
+            assert_eq!(samples.iter().filter(|&&sample| sample == -8 * CHANNEL_AMPLITUDE as i16).count(), 100 - high_samples);
+        }
+    }
+
+    #[test]
+    fn noise_follows_the_shift_register() {
+        let mut sound: SoundGenerator = SoundGenerator::init();
+
+        // Clocking the shift register once per sample walks the seed bit down from bit 14 to bit 0 in 15 samples
+        set_channel(&mut sound, NOISE, SAMPLE_RATE as u16, 4, 0);
+        sound.write(CONTROL, 0b100);
+
+        let samples: Vec<i16> = generate(&mut sound, 15);
+        let low: i16 = -4 * CHANNEL_AMPLITUDE as i16;
+
+        assert_eq!(samples[..14], [low; 14]);
+        assert_eq!(samples[14], -low);
+    }
+}
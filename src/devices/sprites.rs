@@ -0,0 +1,160 @@
+use crate::bus::Device;
+use crate::system::system;
+use std::cell::RefCell;
+
+pub const SPRITE_COUNT: usize = 16;
+pub const SPRITE_SIZE: usize = 8;
+
+// Register layout, relative to the start of the sprite registers
+//  +0 transparent colour: pattern pixels of this value are not drawn
+//  +1 collisions of sprites 0 to 7, one bit per sprite, reading it clears it
+//  +2 collisions of sprites 8 to 15, one bit per sprite, reading it clears it
+//  +$10 sprite attribute table, 6 bytes per sprite:
+//      X, Y, pattern address low byte, pattern address high byte, colour, flags
+const TRANSPARENT_COLOUR: u16 = 0;
+const COLLISIONS_LOW: u16 = 1;
+const COLLISIONS_HIGH: u16 = 2;
+const ATTRIBUTE_TABLE: u16 = 0x10;
+const ATTRIBUTE_SIZE: usize = 6;
+
+const ENABLED: u8 = 0b001;
+const FLIP_HORIZONTAL: u8 = 0b010;
+const FLIP_VERTICAL: u8 = 0b100;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SpriteAttributes {
+    x: u8,
+    y: u8,
+    pattern_address: u16,
+    colour: u8,
+    flags: u8
+}
+
+// A sprite is an 8x8 pattern with one palette index per pixel stored row by row, the colour of the sprite is added to every pixel
+// so one pattern can be drawn in several colours. Sprite 0 is drawn on top of the others
+pub struct Sprites {
+    transparent_colour: u8,
+    collisions: u16,
+    attributes: [SpriteAttributes; SPRITE_COUNT]
+}
+
+impl Sprites {
+    pub fn init() -> Sprites {
+        Sprites {
+            transparent_colour: 0,
+            collisions: 0,
+            attributes: [SpriteAttributes::default(); SPRITE_COUNT]
+        }
+    }
+
+    // Returns the screen position and palette index of every pixel the sprite covers, with transparent pixels left out
+    pub fn visible_pixels(&self, sprite: usize, memory: &system::Memory) -> Vec<(usize, usize, u8)> {
+        let attributes: SpriteAttributes = self.attributes[sprite];
+        let mut to_return: Vec<(usize, usize, u8)> = vec![];
+
+        if attributes.flags & ENABLED == 0 {
+            return to_return;
+        }
+
+        for row in 0..SPRITE_SIZE {
+            for column in 0..SPRITE_SIZE {
+                let pattern_row: usize = if attributes.flags & FLIP_VERTICAL != 0 { SPRITE_SIZE - 1 - row } else { row };
+                let pattern_column: usize = if attributes.flags & FLIP_HORIZONTAL != 0 { SPRITE_SIZE - 1 - column } else { column };
+                let pixel_address: u16 = attributes.pattern_address.wrapping_add((pattern_row * SPRITE_SIZE + pattern_column) as u16);
+                let pixel: u8 = memory.peek_mem_cell_value(pixel_address as usize);
+
+                if pixel != self.transparent_colour {
+                    to_return.push((attributes.x as usize + column, attributes.y as usize + row, pixel.wrapping_add(attributes.colour)));
+                }
+            }
+        }
+
+        to_return
+    }
+}
+
+impl Device for Sprites {
+    fn read(&mut self, offset: u16) -> u8 {
+        let value: u8 = self.peek(offset);
+
+        match offset {
+            COLLISIONS_LOW => self.collisions &= 0xff00,
+            COLLISIONS_HIGH => self.collisions &= 0x00ff,
+
+            _ => { }
+        }
+
+        value
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == TRANSPARENT_COLOUR {
+            self.transparent_colour = value;
+        }
+
+        if offset < ATTRIBUTE_TABLE {
+            return;
+        }
+
+        let table_offset: usize = (offset - ATTRIBUTE_TABLE) as usize;
+
+        if let Some(attributes) = self.attributes.get_mut(table_offset / ATTRIBUTE_SIZE) {
+            match table_offset % ATTRIBUTE_SIZE {
+                0 => attributes.x = value,
+                1 => attributes.y = value,
+                2 => attributes.pattern_address = (attributes.pattern_address & 0xff00) | value as u16,
+                3 => attributes.pattern_address = (attributes.pattern_address & 0x00ff) | (value as u16) << 8,
+                4 => attributes.colour = value,
+                _ => attributes.flags = value
+            }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            TRANSPARENT_COLOUR => self.transparent_colour,
+            COLLISIONS_LOW => self.collisions as u8,
+            COLLISIONS_HIGH => (self.collisions >> 8) as u8,
+            ATTRIBUTE_TABLE.. => {
+                let table_offset: usize = (offset - ATTRIBUTE_TABLE) as usize;
+
+                match self.attributes.get(table_offset / ATTRIBUTE_SIZE) {
+                    Some(attributes) => match table_offset % ATTRIBUTE_SIZE {
+                        0 => attributes.x,
+                        1 => attributes.y,
+                        2 => attributes.pattern_address as u8,
+                        3 => (attributes.pattern_address >> 8) as u8,
+                        4 => attributes.colour,
+                        _ => attributes.flags
+                    },
+                    None => 0
+                }
+            }
+
+            _ => 0
+        }
+    }
+}
+
+// Sets the collision bits of every sprite that shares a screen pixel with another sprite this frame. Run at every vertical blank,
+// the device is only borrowed mutably once the patterns have been read so they may live anywhere in memory
+pub fn detect_collisions(sprites: &RefCell<Sprites>, memory: &system::Memory) {
+    let screen_layout: system::ScreenLayout = memory.get_screen_layout();
+    let mut owners: Vec<Option<usize>> = vec![None; screen_layout.size()];
+    let mut collisions: u16 = 0;
+
+    for sprite in 0..SPRITE_COUNT {
+        for (x, y, _) in sprites.borrow().visible_pixels(sprite, memory) {
+            if x >= screen_layout.width || y >= screen_layout.height {
+                continue;
+            }
+
+            match owners[x + y * screen_layout.width] {
+                Some(other) if other != sprite => collisions |= 1 << sprite | 1 << other,
+                _ => owners[x + y * screen_layout.width] = Some(sprite)
+            }
+        }
+    }
+
+    sprites.borrow_mut().collisions |= collisions;
+}
@@ -0,0 +1,261 @@
+use crate::bus::Device;
+
+// Register layout, relative to the start of the timer, uses the offsets of the 6522 VIA timer registers but not all of their
+// behaviour: there is no pulse counting, bit 5 of the auxiliary control register makes timer 2 free-running instead
+//  +4 timer 1 counter low: reading it acknowledges the timer 1 interrupt, writing it sets the low byte of the latch
+//  +5 timer 1 counter high: writing it sets the high byte of the latch, loads the counter from the latch and starts the timer
+//  +6 timer 1 latch low
+//  +7 timer 1 latch high, writing it acknowledges the timer 1 interrupt
+//  +8 timer 2 counter low: reading it acknowledges the timer 2 interrupt, writing it sets the low byte of the latch
+//  +9 timer 2 counter high: writing it sets the high byte of the latch, loads the counter from the latch and starts the timer
+//  +B auxiliary control: bit 6 makes timer 1 free-running, bit 5 makes timer 2 free-running, otherwise they are one-shot
+//  +D interrupt flags: bit 6 timer 1, bit 5 timer 2, bit 7 is set when an enabled flag is set. Writing a 1 to a bit clears it
+//  +E interrupt enable: writing with bit 7 set enables the given bits, writing with bit 7 clear disables them
+const TIMER_1_COUNTER_LOW: u16 = 0x4;
+const TIMER_1_COUNTER_HIGH: u16 = 0x5;
+const TIMER_1_LATCH_LOW: u16 = 0x6;
+const TIMER_1_LATCH_HIGH: u16 = 0x7;
+const TIMER_2_COUNTER_LOW: u16 = 0x8;
+const TIMER_2_COUNTER_HIGH: u16 = 0x9;
+const AUXILIARY_CONTROL: u16 = 0xb;
+const INTERRUPT_FLAGS: u16 = 0xd;
+const INTERRUPT_ENABLE: u16 = 0xe;
+
+const TIMER_1_FLAG: u8 = 0b0100_0000;
+const TIMER_2_FLAG: u8 = 0b0010_0000;
+
+struct CountdownTimer {
+    counter: u16,
+    latch: u16,
+    running: bool
+}
+
+impl CountdownTimer {
+    fn init() -> CountdownTimer {
+        CountdownTimer {
+            counter: 0,
+            latch: 0,
+            running: false
+        }
+    }
+
+    fn start(&mut self) {
+        self.counter = self.latch;
+        self.running = true;
+    }
+
+    // Counts down and returns true when the timer ran out, a free-running timer reloads from the latch while a one-shot
+    // timer keeps counting down without firing again until it is restarted
+    fn tick(&mut self, cycles: u32, free_running: bool) -> bool {
+        let mut remaining: u32 = cycles;
+        let mut fired: bool = false;
+
+        while self.running && remaining > self.counter as u32 {
+            remaining -= self.counter as u32 + 1;
+            fired = true;
+
+            if free_running {
+                self.counter = self.latch;
+            } else {
+                self.running = false;
+                self.counter = 0xffff;
+            }
+        }
+
+        self.counter = self.counter.wrapping_sub(remaining as u16);
+        fired
+    }
+}
+
+pub struct Timer {
+    timers: [CountdownTimer; 2],
+    auxiliary_control: u8,
+    interrupt_flags: u8,
+    interrupt_enable: u8
+}
+
+impl Timer {
+    pub fn init() -> Timer {
+        Timer {
+            timers: [CountdownTimer::init(), CountdownTimer::init()],
+            auxiliary_control: 0,
+            interrupt_flags: 0,
+            interrupt_enable: 0
+        }
+    }
+}
+
+impl Device for Timer {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            TIMER_1_COUNTER_LOW => self.interrupt_flags &= !TIMER_1_FLAG,
+            TIMER_2_COUNTER_LOW => self.interrupt_flags &= !TIMER_2_FLAG,
+
+            _ => { }
+        }
+
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            TIMER_1_COUNTER_LOW | TIMER_1_LATCH_LOW => self.timers[0].latch = (self.timers[0].latch & 0xff00) | value as u16,
+            TIMER_2_COUNTER_LOW => self.timers[1].latch = (self.timers[1].latch & 0xff00) | value as u16,
+
+            TIMER_1_COUNTER_HIGH | TIMER_2_COUNTER_HIGH => {
+                let timer: usize = if offset == TIMER_1_COUNTER_HIGH { 0 } else { 1 };
+
+                self.timers[timer].latch = (self.timers[timer].latch & 0x00ff) | (value as u16) << 8;
+                self.timers[timer].start();
+                self.interrupt_flags &= if timer == 0 { !TIMER_1_FLAG } else { !TIMER_2_FLAG };
+            },
+
+            TIMER_1_LATCH_HIGH => {
+                self.timers[0].latch = (self.timers[0].latch & 0x00ff) | (value as u16) << 8;
+                self.interrupt_flags &= !TIMER_1_FLAG;
+            },
+
+            AUXILIARY_CONTROL => self.auxiliary_control = value,
+            INTERRUPT_FLAGS => self.interrupt_flags &= !value,
+
+            INTERRUPT_ENABLE => {
+                if value & 0b1000_0000 != 0 {
+                    self.interrupt_enable |= value & 0b0111_1111;
+                } else {
+                    self.interrupt_enable &= !value;
+                }
+            },
+
+            _ => { }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            TIMER_1_COUNTER_LOW => self.timers[0].counter as u8,
+            TIMER_1_COUNTER_HIGH => (self.timers[0].counter >> 8) as u8,
+            TIMER_1_LATCH_LOW => self.timers[0].latch as u8,
+            TIMER_1_LATCH_HIGH => (self.timers[0].latch >> 8) as u8,
+            TIMER_2_COUNTER_LOW => self.timers[1].counter as u8,
+            TIMER_2_COUNTER_HIGH => (self.timers[1].counter >> 8) as u8,
+            AUXILIARY_CONTROL => self.auxiliary_control,
+            INTERRUPT_FLAGS => self.interrupt_flags | if self.irq() { 0b1000_0000 } else { 0 },
+            INTERRUPT_ENABLE => self.interrupt_enable | 0b1000_0000,
+
+            _ => 0
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.interrupt_flags & self.interrupt_enable & (TIMER_1_FLAG | TIMER_2_FLAG) != 0
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if self.timers[0].tick(cycles, self.auxiliary_control & 0b0100_0000 != 0) {
+            self.interrupt_flags |= TIMER_1_FLAG;
+        }
+
+        if self.timers[1].tick(cycles, self.auxiliary_control & 0b0010_0000 != 0) {
+            self.interrupt_flags |= TIMER_2_FLAG;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Loads timer 1 with the given latch value, which also starts it
+    fn start_timer_1(timer: &mut Timer, latch: u16) {
+        timer.write(TIMER_1_COUNTER_LOW, latch as u8);
+        timer.write(TIMER_1_COUNTER_HIGH, (latch >> 8) as u8);
+    }
+
+    #[test]
+    fn starting_a_timer_loads_the_counter_from_the_latch() {
+        let mut timer: Timer = Timer::init();
+
+        start_timer_1(&mut timer, 0x1234);
+        timer.tick(0x34);
+
+        assert_eq!(timer.peek(TIMER_1_LATCH_LOW), 0x34);
+        assert_eq!(timer.peek(TIMER_1_LATCH_HIGH), 0x12);
+        assert_eq!(timer.peek(TIMER_1_COUNTER_HIGH), 0x12);
+        assert_eq!(timer.peek(TIMER_1_COUNTER_LOW), 0x00);
+    }
+
+    #[test]
+    fn one_shot_timer_fires_once() {
+        let mut timer: Timer = Timer::init();
+
+        start_timer_1(&mut timer, 100);
+        timer.tick(100);
+
+        assert_eq!(timer.peek(INTERRUPT_FLAGS) & TIMER_1_FLAG, 0);
+
+        timer.tick(1);
+
+        assert_eq!(timer.peek(INTERRUPT_FLAGS) & TIMER_1_FLAG, TIMER_1_FLAG);
+
+        // Reading the low counter byte acknowledges the interrupt, the stopped timer does not raise it again
+        timer.read(TIMER_1_COUNTER_LOW);
+        timer.tick(0x20000);
+
+        assert_eq!(timer.peek(INTERRUPT_FLAGS) & TIMER_1_FLAG, 0);
+    }
+
+    #[test]
+    fn free_running_timer_reloads_from_the_latch() {
+        let mut timer: Timer = Timer::init();
+
+        timer.write(AUXILIARY_CONTROL, 0b0100_0000);
+        start_timer_1(&mut timer, 100);
+        timer.tick(101);
+
+        assert_eq!(timer.peek(INTERRUPT_FLAGS) & TIMER_1_FLAG, TIMER_1_FLAG);
+        assert_eq!(timer.peek(TIMER_1_COUNTER_LOW), 100);
+
+        timer.read(TIMER_1_COUNTER_LOW);
+        timer.tick(101);
+
+        assert_eq!(timer.peek(INTERRUPT_FLAGS) & TIMER_1_FLAG, TIMER_1_FLAG);
+        assert_eq!(timer.peek(TIMER_1_COUNTER_LOW), 100);
+    }
+
+    #[test]
+    fn writing_a_one_to_an_interrupt_flag_clears_it() {
+        let mut timer: Timer = Timer::init();
+
+        start_timer_1(&mut timer, 0);
+        timer.write(TIMER_2_COUNTER_LOW, 0);
+        timer.write(TIMER_2_COUNTER_HIGH, 0);
+        timer.tick(1);
+
+        assert_eq!(timer.peek(INTERRUPT_FLAGS), TIMER_1_FLAG | TIMER_2_FLAG);
+
+        timer.write(INTERRUPT_FLAGS, TIMER_2_FLAG);
+
+        assert_eq!(timer.peek(INTERRUPT_FLAGS), TIMER_1_FLAG);
+    }
+
+    #[test]
+    fn interrupt_enable_sets_and_clears_bits_by_bit_7() {
+        let mut timer: Timer = Timer::init();
+
+        start_timer_1(&mut timer, 0);
+        timer.tick(1);
+
+        assert!(!timer.irq());
+
+        timer.write(INTERRUPT_ENABLE, 0b1000_0000 | TIMER_1_FLAG | TIMER_2_FLAG);
+
+        assert_eq!(timer.peek(INTERRUPT_ENABLE), 0b1000_0000 | TIMER_1_FLAG | TIMER_2_FLAG);
+        assert!(timer.irq());
+        assert_eq!(timer.peek(INTERRUPT_FLAGS), 0b1000_0000 | TIMER_1_FLAG);
+
+        timer.write(INTERRUPT_ENABLE, TIMER_1_FLAG);
+
+        assert_eq!(timer.peek(INTERRUPT_ENABLE), 0b1000_0000 | TIMER_2_FLAG);
+        assert!(!timer.irq());
+    }
+}
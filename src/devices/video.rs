@@ -0,0 +1,188 @@
+use crate::bus::Device;
+use crate::system::system::CPU_CLOCK_HZ;
+
+// Register layout, relative to the start of the video registers
+//  +0 status: bit 7 is set at the start of every vertical blank and cleared when this register is read
+//  +1 control: bit 0 raises an NMI at every vertical blank
+//  +2 frame counter low byte
+//  +3 frame counter high byte
+//  +4 display mode, see the DISPLAY_MODE_ constants
+//  +5 horizontal scroll in pixels
+//  +6 vertical scroll in pixels
+const STATUS: u16 = 0;
+const CONTROL: u16 = 1;
+const FRAME_COUNTER_LOW: u16 = 2;
+const FRAME_COUNTER_HIGH: u16 = 3;
+const DISPLAY_MODE: u16 = 4;
+const SCROLL_X: u16 = 5;
+const SCROLL_Y: u16 = 6;
+
+// Every screen byte picks one of the first 16 palette entries with its low nibble
+pub const DISPLAY_MODE_16_COLOURS: u8 = 0;
+// Every screen byte picks any of the 256 palette entries
+pub const DISPLAY_MODE_256_COLOURS: u8 = 1;
+// The screen shows the text map drawn with the character RAM instead of the screen memory
+pub const DISPLAY_MODE_TEXT: u8 = 2;
+
+const VBLANK_FLAG: u8 = 0b1000_0000;
+
+// Keeps the display timing, a frame lasts a fixed number of CPU cycles derived from the refresh rate
+pub struct Video {
+    cycles_per_frame: u32,
+    cycles_into_frame: u32,
+    vblank: bool,
+    control: u8,
+    display_mode: u8,
+    scroll_x: u8,
+    scroll_y: u8,
+    frame_counter: u16,
+    frame_ended: bool,
+    nmi_pending: bool
+}
+
+impl Video {
+    pub fn init(refresh_rate: u32) -> Video {
+        Video {
+            cycles_per_frame: CPU_CLOCK_HZ / refresh_rate.max(1),
+            cycles_into_frame: 0,
+            vblank: false,
+            control: 0,
+            display_mode: DISPLAY_MODE_16_COLOURS,
+            scroll_x: 0,
+            scroll_y: 0,
+            frame_counter: 0,
+            frame_ended: false,
+            nmi_pending: false
+        }
+    }
+
+    pub fn display_mode(&self) -> u8 {
+        self.display_mode
+    }
+
+    pub fn scroll(&self) -> (usize, usize) {
+        (self.scroll_x as usize, self.scroll_y as usize)
+    }
+
+    pub fn cycles_until_vblank(&self) -> u32 {
+        self.cycles_per_frame - self.cycles_into_frame
+    }
+
+    // Returns true once after every vertical blank
+    pub fn take_frame_end(&mut self) -> bool {
+        std::mem::replace(&mut self.frame_ended, false)
+    }
+
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::replace(&mut self.nmi_pending, false)
+    }
+}
+
+impl Device for Video {
+    fn read(&mut self, offset: u16) -> u8 {
+        let value: u8 = self.peek(offset);
+
+        if offset == STATUS {
+            self.vblank = false;
+        }
+
+        value
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            CONTROL => self.control = value,
+            DISPLAY_MODE => self.display_mode = value,
+            SCROLL_X => self.scroll_x = value,
+            SCROLL_Y => self.scroll_y = value,
+
+            _ => { }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            STATUS if self.vblank => VBLANK_FLAG,
+            CONTROL => self.control,
+            FRAME_COUNTER_LOW => self.frame_counter as u8,
+            FRAME_COUNTER_HIGH => (self.frame_counter >> 8) as u8,
+            DISPLAY_MODE => self.display_mode,
+            SCROLL_X => self.scroll_x,
+            SCROLL_Y => self.scroll_y,
+
+            _ => 0
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles_into_frame += cycles;
+
+        while self.cycles_into_frame >= self.cycles_per_frame {
+            self.cycles_into_frame -= self.cycles_per_frame;
+            self.vblank = true;
+            self.frame_ended = true;
+            self.frame_counter = self.frame_counter.wrapping_add(1);
+
+            if self.control & 1 != 0 {
+                self.nmi_pending = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::system::{self, Memory, ScreenLayout};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn vertical_blank_comes_every_frame() {
+        let mut video: Video = Video::init(50);
+
+        video.write(CONTROL, 1);
+        video.tick(CPU_CLOCK_HZ / 50 - 1);
+
+        assert!(!video.take_frame_end());
+        assert!(!video.take_nmi());
+        assert_eq!(video.peek(STATUS), 0);
+
+        video.tick(1);
+
+        assert!(video.take_frame_end());
+        assert!(video.take_nmi());
+        assert_eq!(video.peek(STATUS), VBLANK_FLAG);
+        assert_eq!(video.cycles_until_vblank(), CPU_CLOCK_HZ / 50);
+
+        // Two frames at once raise the flag and count both frames
+        video.tick(2 * CPU_CLOCK_HZ / 50);
+
+        assert!(video.take_nmi());
+        assert!(!video.take_nmi());
+        assert_eq!(video.peek(FRAME_COUNTER_LOW), 3);
+    }
+
+    #[test]
+    fn no_nmi_without_the_control_bit() {
+        let mut video: Video = Video::init(60);
+
+        video.tick(CPU_CLOCK_HZ / 60);
+
+        assert!(video.take_frame_end());
+        assert!(!video.take_nmi());
+        assert_eq!(video.peek(STATUS), VBLANK_FLAG);
+    }
+
+    #[test]
+    fn reading_the_status_register_clears_the_vertical_blank_flag() {
+        let video: Rc<RefCell<Video>> = Rc::new(RefCell::new(Video::init(60)));
+        let mut memory: Memory = Memory::init(0, ScreenLayout { width: system::DEFAULT_SCREEN_WIDTH, height: system::DEFAULT_SCREEN_HEIGHT, start_address: system::DEFAULT_SCREEN_MEMORY_START });
+
+        memory.attach_device(system::VIDEO_START, system::VIDEO_END, Box::new(Rc::clone(&video)));
+        video.borrow_mut().tick(CPU_CLOCK_HZ / 60);
+
+        assert_eq!(memory.get_mem_cell_value(0xd060), VBLANK_FLAG);
+        assert_eq!(memory.get_mem_cell_value(0xd060), 0);
+    }
+}
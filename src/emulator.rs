@@ -1,8 +1,35 @@
 use crate::analyze_code::AddressingMode;
+#[cfg(feature = "audio")]
+use crate::audio_output::HostAudio;
+use crate::audio_output::WavWriter;
+use crate::config::{Config, Screenshot, ScreenshotTime};
+use crate::breakpoints::{self, Breakpoints, Hit};
+use crate::debugger::{self, DebugView, Monitor, MonitorAction, StopCondition};
+use crate::devices::blitter::{self, Blitter};
+use crate::devices::bank_switcher::{BankedMemory, BankSelect, BankWindow, BANK_SIZE};
+use crate::devices::keyboard::Keyboard;
+use crate::devices::joystick::Joystick;
+use crate::devices::mouse::Mouse;
+use crate::devices::sound::SoundGenerator;
+use crate::devices::file_io::{self, FileIo};
+use crate::devices::palette::Palette;
+use crate::devices::ram::Ram;
+use crate::devices::serial::{self, Serial};
+use crate::devices::sprites::{self, Sprites};
+use crate::devices::timer::Timer;
+use crate::devices::video::Video;
+use crate::{font, framebuffer, gdb_stub};
+use crate::input_script::{self, InputEvent, InputRecorder, InputScript};
+use crate::read_file;
+use crate::video_output::{GifRecorder, RawFrameWriter};
 use crate::system::system;
 use crate::window::Window;
-use crate::{analyze_code::{Instruction, Opcode}, instruction_functions as instruction};
-use rand::Rng;
+use crate::{analyze_code::{DataBlock, Instruction, Opcode}, instruction_functions as instruction};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use image::RgbaImage;
+use piston_window::RenderEvent;
 
 macro_rules! increment_instruction_index {
     ($index: expr, $instruction_vector_length: expr) => {
@@ -14,129 +41,749 @@ macro_rules! increment_instruction_index {
     };
 }
 
-struct Vp8System {
+const IRQ_HANDLER_LABEL: &str = "irq";
+const NMI_HANDLER_LABEL: &str = "nmi";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExecutionStatus {
+    Running,
+    Frame,
+    Break
+}
+
+pub struct Vp8System {
     pub registers: system::Registers,
     pub memory: system::Memory,
     pub flags: system::Flags,
+    pub interrupt_returns: Vec<(usize, system::Flags)>,
+    pub cycles: u64
+}
+
+// Handles to the devices the host feeds with input, the same devices are also attached to the memory map
+struct Peripherals {
+    keyboard: Rc<RefCell<Keyboard>>,
+    mouse: Rc<RefCell<Mouse>>,
+    joystick: Rc<RefCell<Joystick>>,
+    sound: Rc<RefCell<SoundGenerator>>,
+    video: Rc<RefCell<Video>>,
+    palette: Rc<RefCell<Palette>>,
+    sprites: Rc<RefCell<Sprites>>,
+    file_io: Rc<RefCell<FileIo>>,
+    blitter: Rc<RefCell<Blitter>>,
+    serial: Rc<RefCell<Serial>>
+}
+
+// The sound device produces samples while the program runs, they are handed to these outputs after every frame
+struct AudioOutputs {
+    wav_writer: Option<WavWriter>,
+    #[cfg(feature = "audio")]
+    host_audio: Option<HostAudio>
 }
 
-pub fn start_emulator(instructions: Vec<Instruction>) {
+// Recordings of every frame the display shows
+struct VideoOutputs {
+    gif_recorder: Option<GifRecorder>,
+    raw_frame_writer: Option<RawFrameWriter>
+}
+
+// Where input comes from and where it goes: a replayed input script replaces the live input of the window,
+// and every applied event is written to the input log when recording
+struct InputHandler {
+    script: Option<InputScript>,
+    recorder: Option<InputRecorder>,
+    // Live serial input, disconnected once stdin has ended
+    serial_stdin: Option<Receiver<u8>>,
+    joystick_bindings: [u8; 8]
+}
+
+// What a run left behind when it ended, the test harness compares it with the expected results
+pub struct FinalState {
+    pub registers: system::Registers,
+    pub halted: bool,
+    pub cycles: u64,
+    pub memory: Vec<u8>,
+    pub screen: RgbaImage
+}
+
+pub fn start_emulator(instructions: Vec<Instruction>, data_blocks: Vec<DataBlock>, config: Config) -> FinalState {
+    let script: Option<InputScript> = config.input_script.clone().map(input_script::read_input_script);
+    let seed: u64 = script.as_ref().and_then(|script| script.seed).or(config.seed).unwrap_or_else(rand::random);
+
     let mut vp8: Vp8System = Vp8System { 
         registers: system::Registers::init(),
         flags: system::Flags::init(),
-        memory: system::Memory::init()
+        memory: system::Memory::init(seed, config.screen_layout),
+        interrupt_returns: vec![],
+        cycles: 0
+    };
+
+    load_memory(&mut vp8.memory, data_blocks, &config);
+
+    // Stdin is live input like the window, a replayed script brings its own serial input, the monitor of --debug owns stdin
+    // and a run driven by a remote debugger or the test runner has to behave the same whatever stdin holds
+    let reads_stdin: bool = config.serial_stdin && config.serial_in.is_none() && script.is_none() && !config.debug && config.gdb.is_none();
+    let serial_stdin: Option<Receiver<u8>> = if reads_stdin { Some(serial::spawn_stdin_reader()) } else { None };
+    let scripted_serial_input: bool = script.as_ref().map_or(false, |script| script.events.iter().any(|scripted_event| matches!(scripted_event.event, InputEvent::SerialByte(_) | InputEvent::SerialEnd)));
+
+    let peripherals: Peripherals = attach_peripherals(&mut vp8.memory, &config, serial_stdin.is_some() || scripted_serial_input);
+    let mut input_handler: InputHandler = InputHandler {
+        script,
+        recorder: config.record_input.clone().map(|path| InputRecorder::create(path, seed)),
+        serial_stdin,
+        joystick_bindings: config.joystick_bindings
+    };
+
+    let mut audio_outputs: AudioOutputs = AudioOutputs {
+        wav_writer: config.wav.clone().map(WavWriter::create),
+        #[cfg(feature = "audio")]
+        host_audio: if config.headless || config.debug || config.gdb.is_some() { None } else { HostAudio::init() }
+    };
+
+    let mut video_outputs: VideoOutputs = VideoOutputs {
+        gif_recorder: config.record.clone().map(|path| GifRecorder::create(path, config.refresh_rate)),
+        raw_frame_writer: config.record_raw.clone().map(RawFrameWriter::create)
+    };
+
+    let halted: bool = if config.debug || config.gdb.is_some() {
+        let mut target: DebugTarget = DebugTarget::init(&instructions, &mut vp8, &peripherals, &mut input_handler, &mut audio_outputs, &mut video_outputs, &config);
+
+        match &config.gdb {
+            Some(address) => gdb_stub::serve(address, &mut target),
+            None => run_debugger(&mut target)
+        }
+    } else if config.headless {
+        run_headless(&instructions, &mut vp8, &peripherals, &mut input_handler, &mut audio_outputs, &mut video_outputs, &config)
+    } else {
+        run_windowed(&instructions, &mut vp8, &peripherals, &mut input_handler, &mut audio_outputs, &mut video_outputs, &config)
     };
 
-    let mut game_window: Window = Window::init();
+    FinalState {
+        registers: vp8.registers,
+        halted,
+        cycles: vp8.cycles,
+        memory: (0..=0xffff).map(|address| vp8.memory.peek_mem_cell_value(address)).collect(),
+        screen: render_screen(&vp8, &peripherals, &config)
+    }
+}
 
-    let labels: Vec<(String, usize)> = get_labels(instructions.clone());
+// The window paces the emulation, every render event runs the program for one frame and then draws the screen.
+// Returns whether the program stopped at BRK before the window was closed
+fn run_windowed(instructions: &[Instruction], vp8: &mut Vp8System, peripherals: &Peripherals, input_handler: &mut InputHandler, audio_outputs: &mut AudioOutputs, video_outputs: &mut VideoOutputs, config: &Config) -> bool {
+    let mut game_window: Window = Window::init(config.refresh_rate, config.screen_layout, config.pixel_scale);
+
+    let labels: Vec<(String, usize)> = get_labels(instructions.to_vec());
     let mut routines: Vec<usize> = vec![];
     let mut index: usize = 0;
+    let mut frame: u64 = 0;
+    let mut halted: bool = false;
 
     while let Some(event) = game_window.get_window_next() {
-        execute_code(&instructions, &mut index, &mut vp8, labels.clone(), &mut routines);
+        if input_handler.script.is_none() {
+            for input_event in game_window.convert_event_to_input_events(&event) {
+                apply_input_event(input_event, frame, peripherals, input_handler);
+            }
+        }
+
+        if Window::is_screenshot_request(&event) {
+            let path: String = format!("vp8-screenshot-{frame}.png");
+
+            save_screenshot(&path, vp8, peripherals, config);
+            println!("Saved a screenshot to {path}");
+        }
+
+        if event.render_args().is_none() {
+            continue;
+        }
+
+        if !halted {
+            apply_scripted_events(frame, peripherals, input_handler);
+            halted = execute_code(instructions, &mut index, vp8, peripherals, &labels, &mut routines) == ExecutionStatus::Break;
+            output_audio(peripherals, audio_outputs);
+            frame += 1;
+
+            let screen_image: RgbaImage = render_screen(vp8, peripherals, config);
+
+            output_video(&screen_image, video_outputs);
+            game_window.set_screen_image(screen_image);
+        }
 
-        game_window.set_screen_memory_data(vp8.memory.get_screen_memory());
         game_window.update(event);
+    }
+
+    halted
+}
+
+// Runs without a window as fast as possible, input comes from the input script instead of the keyboard.
+// Returns whether the program stopped at BRK, rather than at the frame or cycle limit
+fn run_headless(instructions: &[Instruction], vp8: &mut Vp8System, peripherals: &Peripherals, input_handler: &mut InputHandler, audio_outputs: &mut AudioOutputs, video_outputs: &mut VideoOutputs, config: &Config) -> bool {
+    let labels: Vec<(String, usize)> = get_labels(instructions.to_vec());
+    let mut routines: Vec<usize> = vec![];
+    let mut index: usize = 0;
+    let mut frame: u64 = 0;
+    let mut screenshots: Vec<Screenshot> = config.screenshots.clone();
+    let mut halted: bool = false;
+
+    loop {
+        save_due_screenshots(&mut screenshots, |time| time == ScreenshotTime::Frame(frame), vp8, peripherals, config);
+        apply_scripted_events(frame, peripherals, input_handler);
+
+        // Runs the frame one instruction at a time, so screenshots waiting for a cycle show the screen at exactly that point
+        let status: ExecutionStatus = loop {
+            let status: ExecutionStatus = execute_instruction(instructions, &mut index, vp8, peripherals, &labels, &mut routines);
+            let cycles: u64 = vp8.cycles;
+
+            save_due_screenshots(&mut screenshots, |time| matches!(time, ScreenshotTime::Cycle(cycle) if cycles >= cycle), vp8, peripherals, config);
 
-        index = increment_instruction_index!(index, instructions.len());
+            if status != ExecutionStatus::Running || config.cycles.map_or(false, |limit| cycles >= limit) {
+                break status;
+            }
+        };
+
+        output_audio(peripherals, audio_outputs);
+
+        // Rendering is only needed for the recordings, nothing is displayed
+        if video_outputs.gif_recorder.is_some() || video_outputs.raw_frame_writer.is_some() {
+            output_video(&render_screen(vp8, peripherals, config), video_outputs);
+        }
+
+        if status != ExecutionStatus::Break && config.cycles.map_or(false, |limit| vp8.cycles >= limit) {
+            break;
+        }
+
+        frame += 1;
+
+        // A program stopped at BRK keeps its screen, so the screenshots due after its last frame are still taken
+        save_due_screenshots(&mut screenshots, |time| time == ScreenshotTime::Frame(frame), vp8, peripherals, config);
+
+        if status == ExecutionStatus::Break {
+            halted = true;
+            break;
+        }
+
+        if config.frames.map_or(false, |frames| frame >= frames) {
+            break;
+        }
+    }
+
+    for (_, path) in screenshots {
+        println!("The screenshot {path} was not saved, the program stopped before it was due");
+    }
+
+    halted
+}
+
+// Why a run started by a debugger stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugStop {
+    Reached,
+    Hits(Vec<Hit>),
+    Halted,
+    CycleLimit,
+    Interrupted
+}
+
+// How often a run started by a debugger asks whether the debugger wants it interrupted, in instructions
+const INTERRUPT_CHECK_INTERVAL: u64 = 4096;
+
+// The machine and the position in the program while a debugger controls it, there is no window and frames end as the program reaches them
+pub struct DebugTarget<'a> {
+    instructions: &'a [Instruction],
+    pub vp8: &'a mut Vp8System,
+    peripherals: &'a Peripherals,
+    input_handler: &'a mut InputHandler,
+    audio_outputs: &'a mut AudioOutputs,
+    video_outputs: &'a mut VideoOutputs,
+    config: &'a Config,
+    pub labels: Vec<(String, usize)>,
+    pub routines: Vec<usize>,
+    pub index: usize,
+    pub frame: u64,
+    pub halted: bool
+}
+
+impl<'a> DebugTarget<'a> {
+    fn init(instructions: &'a [Instruction], vp8: &'a mut Vp8System, peripherals: &'a Peripherals, input_handler: &'a mut InputHandler, audio_outputs: &'a mut AudioOutputs, video_outputs: &'a mut VideoOutputs, config: &'a Config) -> DebugTarget<'a> {
+        apply_scripted_events(0, peripherals, input_handler);
+
+        // Running a label only moves past it, so the debugger starts at the first instruction
+        let mut index: usize = 0;
+
+        while index + 1 < instructions.len() && instructions[index].opcode == Opcode::LABEL {
+            index += 1;
+        }
+
+        DebugTarget {
+            instructions,
+            vp8,
+            peripherals,
+            input_handler,
+            audio_outputs,
+            video_outputs,
+            config,
+            labels: get_labels(instructions.to_vec()),
+            routines: vec![],
+            index,
+            frame: 0,
+            halted: false
+        }
+    }
+
+    pub fn instructions(&self) -> &'a [Instruction] {
+        self.instructions
+    }
+
+    // Runs until the stop condition, a breakpoint, a watchpoint, BRK or the --cycles limit, or until is_interrupted returns true
+    pub fn run(&mut self, stop_condition: &mut StopCondition, breakpoints: &mut Breakpoints, is_interrupted: &mut dyn FnMut() -> bool) -> DebugStop {
+        if self.halted {
+            return DebugStop::Halted;
+        }
+
+        // Watchpoints need every read and write of the program, the log also drops the writes the debugger made
+        self.vp8.memory.set_access_logging(breakpoints.has_watchpoints());
+
+        let mut executed: u64 = 0;
+
+        loop {
+            let executed_index: usize = self.index;
+            let status: ExecutionStatus = execute_instruction(self.instructions, &mut self.index, self.vp8, self.peripherals, &self.labels, &mut self.routines);
+            let hits: Vec<Hit> = breakpoints.check(executed_index, &self.vp8.memory.take_accesses(), self.index, self.vp8);
+
+            if status == ExecutionStatus::Frame {
+                output_audio(self.peripherals, self.audio_outputs);
+
+                if self.video_outputs.gif_recorder.is_some() || self.video_outputs.raw_frame_writer.is_some() {
+                    output_video(&render_screen(self.vp8, self.peripherals, self.config), self.video_outputs);
+                }
+
+                self.frame += 1;
+                apply_scripted_events(self.frame, self.peripherals, self.input_handler);
+            }
+
+            if status == ExecutionStatus::Break {
+                self.halted = true;
+                return DebugStop::Halted;
+            }
+
+            if !hits.is_empty() {
+                return DebugStop::Hits(hits);
+            }
+
+            // The --cycles limit gets control back from a program that never reaches the stop condition
+            if self.config.cycles.map_or(false, |limit| self.vp8.cycles >= limit) {
+                return DebugStop::CycleLimit;
+            }
+
+            executed += 1;
+
+            if executed % INTERRUPT_CHECK_INTERVAL == 0 && is_interrupted() {
+                return DebugStop::Interrupted;
+            }
+
+            // A label is not an instruction of its own, it is run without counting as a step and a run never stops on it
+            if self.instructions[self.index].opcode == Opcode::LABEL {
+                continue;
+            }
+
+            if stop_condition.is_reached(self.index, self.routines.len(), self.vp8.interrupt_returns.len()) {
+                return DebugStop::Reached;
+            }
+        }
     }
 }
 
-fn execute_code(instructions: &Vec<Instruction>, index: &mut usize, vp8: &mut Vp8System, labels: Vec<(String, usize)>, routines: &mut Vec<usize>) {
+// The monitor of --debug decides when the program runs.
+// Returns whether the program stopped at BRK before the monitor was left
+fn run_debugger(target: &mut DebugTarget) -> bool {
+    let mut monitor: Monitor = Monitor::init();
+
+    println!("{}", debugger::format_instruction(target.instructions, target.index, target.index));
+
     loop {
-        let address: u16 = instructions[*index].value;
-        let addressing_mode: AddressingMode = instructions[*index].addressing_mode;
-        let label_name: String = instructions[*index].label_name.clone();
-
-        match instructions[*index].opcode {
-            Opcode::ADC => instruction::adc(address, addressing_mode, &mut vp8.registers, &mut vp8.flags, vp8.memory),
-            Opcode::AND => instruction::and(address, addressing_mode, &mut vp8.registers, vp8.memory),
-            Opcode::ASL => instruction::asl(address, addressing_mode, &mut vp8.registers, &mut vp8.flags, &mut vp8.memory),
-            Opcode::BIT => instruction::bit(address, addressing_mode, &mut vp8.flags, vp8.memory),
-            Opcode::BCC => *index = instruction::bcc(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::BCS => *index = instruction::bcs(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::BEQ => *index = instruction::beq(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::BMI => *index = instruction::bmi(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::BNE => *index = instruction::bne(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::BPL => *index = instruction::bpl(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::BVC => *index = instruction::bvc(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::BVS => *index = instruction::bvs(*index, &mut vp8.flags, label_name.clone(), labels.clone()),
-            Opcode::CLC => instruction::clc(&mut vp8.flags),
-            Opcode::CLD => instruction::cld(&mut vp8.flags),
-            Opcode::CLI => instruction::cli(&mut vp8.flags),
-            Opcode::CLV => instruction::clv(&mut vp8.flags),
-            Opcode::CMP => instruction::cmp(address, addressing_mode, vp8.registers, &mut vp8.flags, vp8.memory),
-            Opcode::CPX => instruction::cpx(address, addressing_mode, vp8.registers, &mut vp8.flags, vp8.memory),
-            Opcode::CPY => instruction::cpy(address, addressing_mode, vp8.registers, &mut vp8.flags, vp8.memory),
-            Opcode::DEC => instruction::dec(address, addressing_mode, vp8.registers, &mut vp8.memory),
-            Opcode::DEX => instruction::dex(&mut vp8.registers),
-            Opcode::DEY => instruction::dey(&mut vp8.registers),
-            Opcode::EOR => instruction::eor(address, addressing_mode, vp8.memory, &mut vp8.registers),
-            Opcode::INC => instruction::inc(address, addressing_mode, vp8.registers, &mut vp8.memory),
-            Opcode::INX => instruction::inx(&mut vp8.registers),
-            Opcode::INY => instruction::iny(&mut vp8.registers),
-            Opcode::JMP => *index = instruction::jmp(instructions[*index].label_name.clone(), labels.clone()),
-            Opcode::LDA => instruction::lda(address, addressing_mode, vp8.memory, &mut vp8.registers),
-            Opcode::LDX => instruction::ldx(address, addressing_mode, vp8.memory, &mut vp8.registers),
-            Opcode::LDY => instruction::ldy(address, addressing_mode, vp8.memory, &mut vp8.registers),
-            Opcode::LSR => instruction::lsr(address, addressing_mode, &mut vp8.memory, &mut vp8.registers),
-            Opcode::ORA => instruction::ora(address, addressing_mode, &mut vp8.registers, vp8.memory),
-            Opcode::PLA => instruction::pla(&mut vp8.registers, &mut vp8.memory),
-            Opcode::PHA => instruction::pha(vp8.registers, &mut vp8.memory),
-            Opcode::PHP => instruction::php(vp8.flags, &mut vp8.memory),
-            Opcode::PLP => instruction::plp(&mut vp8.flags, &mut vp8.memory),
-            Opcode::ROL => instruction::rol(address, addressing_mode, &mut vp8.registers, vp8.memory, &mut vp8.flags),
-            Opcode::ROR => instruction::ror(address, addressing_mode, &mut vp8.registers, vp8.memory, &mut vp8.flags),
-            Opcode::SBC => instruction::sbc(address, addressing_mode, &mut vp8.registers, &mut vp8.flags, vp8.memory),
-            Opcode::SEC => instruction::sec(&mut vp8.flags),
-            Opcode::SED => instruction::sed(&mut vp8.flags),
-            Opcode::SEI => instruction::sei(&mut vp8.flags),
-            Opcode::STA => instruction::sta(address, addressing_mode, vp8.registers, &mut vp8.memory),
-            Opcode::STX => instruction::stx(address, addressing_mode, vp8.registers, &mut vp8.memory),
-            Opcode::STY => instruction::sty(address, addressing_mode, vp8.registers, &mut vp8.memory),
-            Opcode::TAX => instruction::tax(&mut vp8.registers),
-            Opcode::TAY => instruction::tay(&mut vp8.registers),
-            Opcode::TSX => instruction::tsx(&mut vp8.registers),
-            Opcode::TXA => instruction::txa(&mut vp8.registers),
-            Opcode::TXS => instruction::txs(&mut vp8.registers),
-            Opcode::TYA => instruction::tya(&mut vp8.registers),
-            Opcode::BRK => *index -= 1,
-            
-            Opcode::DRW => break,
-            
-            Opcode::JSR => {
-                routines.push(*index);
-                *index = instruction::jmp(instructions[*index].label_name.clone(), labels.clone());
-            },
-
-            Opcode::RTS => {
-                if routines.len() == 0 {
-                    panic!("No routine ro return from, instruction {:?} at {}", instructions[*index], *index);
+        let view: DebugView = DebugView {
+            instructions: target.instructions,
+            labels: &target.labels,
+            index: target.index,
+            routine_depth: target.routines.len(),
+            frame: target.frame
+        };
+
+        let mut stop_condition: StopCondition = match monitor.next_action(&view, target.vp8) {
+            MonitorAction::Run(stop_condition) => stop_condition,
+            MonitorAction::Quit => break
+        };
+
+        if target.halted {
+            println!("The program stopped at BRK, it cannot run any further");
+            continue;
+        }
+
+        match target.run(&mut stop_condition, &mut monitor.breakpoints, &mut || false) {
+            DebugStop::Hits(hits) => {
+                for hit in hits.iter() {
+                    println!("{}", breakpoints::format_hit(hit, target.instructions));
                 }
+            }
+            DebugStop::Halted => println!("The program stopped at BRK"),
+            DebugStop::CycleLimit => println!("Reached the cycle limit of {}", target.vp8.cycles),
+
+            DebugStop::Reached | DebugStop::Interrupted => { }
+        }
+
+        println!("{}", debugger::format_instruction(target.instructions, target.index, target.index));
+    }
+
+    target.halted
+}
+
+fn save_due_screenshots(screenshots: &mut Vec<Screenshot>, is_due: impl Fn(ScreenshotTime) -> bool, vp8: &Vp8System, peripherals: &Peripherals, config: &Config) {
+    if !screenshots.iter().any(|(time, _)| is_due(*time)) {
+        return;
+    }
+
+    let (due, pending): (Vec<Screenshot>, Vec<Screenshot>) = screenshots.drain(..).partition(|(time, _)| is_due(*time));
+
+    *screenshots = pending;
+
+    for (_, path) in due {
+        save_screenshot(&path, vp8, peripherals, config);
+    }
+}
+
+fn save_screenshot(path: &str, vp8: &Vp8System, peripherals: &Peripherals, config: &Config) {
+    render_screen(vp8, peripherals, config).save(path).unwrap_or_else(|error| panic!("Error saving the screenshot {path}: {error}"));
+}
+
+fn render_screen(vp8: &Vp8System, peripherals: &Peripherals, config: &Config) -> RgbaImage {
+    framebuffer::render(config.screen_layout, &vp8.memory, &peripherals.video.borrow(), &peripherals.palette.borrow(), &peripherals.sprites.borrow())
+}
+
+fn attach_peripherals(memory: &mut system::Memory, config: &Config, receives_serial_input_events: bool) -> Peripherals {
+    let keyboard: Rc<RefCell<Keyboard>> = Rc::new(RefCell::new(Keyboard::init()));
+    let mouse: Rc<RefCell<Mouse>> = Rc::new(RefCell::new(Mouse::init()));
+    let joystick: Rc<RefCell<Joystick>> = Rc::new(RefCell::new(Joystick::init()));
+    let sound: Rc<RefCell<SoundGenerator>> = Rc::new(RefCell::new(SoundGenerator::init()));
+    let video: Rc<RefCell<Video>> = Rc::new(RefCell::new(Video::init(config.refresh_rate)));
+    let palette: Rc<RefCell<Palette>> = Rc::new(RefCell::new(Palette::init()));
+    let sprites: Rc<RefCell<Sprites>> = Rc::new(RefCell::new(Sprites::init()));
+    let file_io: Rc<RefCell<FileIo>> = Rc::new(RefCell::new(FileIo::init(config.file_sandbox.clone())));
+    let blitter: Rc<RefCell<Blitter>> = Rc::new(RefCell::new(Blitter::init(config.blit_cycles_per_byte)));
+    let serial: Rc<RefCell<Serial>> = Rc::new(RefCell::new(Serial::init(config.serial_out.clone(), config.serial_in.clone(), receives_serial_input_events)));
+
+    memory.attach_device(system::KEYBOARD_START, system::KEYBOARD_END, Box::new(Rc::clone(&keyboard)));
+    memory.attach_device(system::MOUSE_START, system::MOUSE_END, Box::new(Rc::clone(&mouse)));
+    memory.attach_device(system::JOYSTICK_ADDRESS, system::JOYSTICK_ADDRESS, Box::new(Rc::clone(&joystick)));
+    memory.attach_device(system::SOUND_START, system::SOUND_END, Box::new(Rc::clone(&sound)));
+    memory.attach_device(system::TIMER_START, system::TIMER_END, Box::new(Timer::init()));
+    memory.attach_device(system::VIDEO_START, system::VIDEO_END, Box::new(Rc::clone(&video)));
+    memory.attach_device(system::SERIAL_START, system::SERIAL_END, Box::new(Rc::clone(&serial)));
+    memory.attach_device(system::PALETTE_START, system::PALETTE_END, Box::new(Rc::clone(&palette)));
+    memory.attach_device(system::CHARACTER_RAM_START, system::CHARACTER_RAM_END, Box::new(Ram::init_with_contents(font::character_set())));
+    memory.attach_device(system::FILE_IO_START, system::FILE_IO_END, Box::new(Rc::clone(&file_io)));
+    memory.attach_device(system::SPRITES_START, system::SPRITES_END, Box::new(Rc::clone(&sprites)));
+    memory.attach_device(system::BLITTER_START, system::BLITTER_END, Box::new(Rc::clone(&blitter)));
+
+    Peripherals {
+        keyboard,
+        mouse,
+        joystick,
+        sound,
+        video,
+        palette,
+        sprites,
+        file_io,
+        blitter,
+        serial
+    }
+}
 
-                *index = routines.pop().unwrap();
-            },
+fn output_video(screen_image: &RgbaImage, video_outputs: &mut VideoOutputs) {
+    if let Some(gif_recorder) = video_outputs.gif_recorder.as_mut() {
+        gif_recorder.record_frame(screen_image);
+    }
+
+    if let Some(raw_frame_writer) = video_outputs.raw_frame_writer.as_mut() {
+        raw_frame_writer.record_frame(screen_image);
+    }
+}
+
+fn output_audio(peripherals: &Peripherals, audio_outputs: &mut AudioOutputs) {
+    let samples: Vec<i16> = peripherals.sound.borrow_mut().take_samples();
+
+    if let Some(wav_writer) = audio_outputs.wav_writer.as_mut() {
+        wav_writer.write_samples(&samples);
+    }
+
+    #[cfg(feature = "audio")]
+    if let Some(host_audio) = audio_outputs.host_audio.as_mut() {
+        host_audio.play_samples(samples);
+    }
+}
+
+// Also hands the bytes read from stdin since the last frame to the serial port, as input events so they are recorded
+fn apply_scripted_events(frame: u64, peripherals: &Peripherals, input_handler: &mut InputHandler) {
+    let stdin_events: Vec<InputEvent> = match &input_handler.serial_stdin {
+        Some(receiver) => {
+            let mut events: Vec<InputEvent> = vec![];
+
+            loop {
+                match receiver.try_recv() {
+                    Ok(byte) => events.push(InputEvent::SerialByte(byte)),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        events.push(InputEvent::SerialEnd);
+                        break;
+                    }
+                }
+            }
 
-            _ => { }
+            events
         }
+        None => vec![]
+    };
+
+    if stdin_events.last() == Some(&InputEvent::SerialEnd) {
+        input_handler.serial_stdin = None;
+    }
+
+    for event in stdin_events {
+        apply_input_event(event, frame, peripherals, input_handler);
+    }
+
+    let scripted_events: Vec<InputEvent> = match &input_handler.script {
+        Some(script) => script.events.iter().filter(|scripted_event| scripted_event.frame == frame).map(|scripted_event| scripted_event.event).collect(),
+        None => return
+    };
+
+    for event in scripted_events {
+        apply_input_event(event, frame, peripherals, input_handler);
+    }
+}
+
+fn apply_input_event(event: InputEvent, frame: u64, peripherals: &Peripherals, input_handler: &mut InputHandler) {
+    if let Some(recorder) = input_handler.recorder.as_mut() {
+        recorder.record(frame, event);
+    }
+
+    match event {
+        InputEvent::KeyPress(key_code) => peripherals.keyboard.borrow_mut().press(key_code),
+        InputEvent::KeyRelease(key_code) => peripherals.keyboard.borrow_mut().release(key_code),
+        InputEvent::MouseMove(x, y) => peripherals.mouse.borrow_mut().move_to(x, y),
+        InputEvent::MousePress(button) => peripherals.mouse.borrow_mut().press(button),
+        InputEvent::MouseRelease(button) => peripherals.mouse.borrow_mut().release(button),
+        InputEvent::JoystickPress(button) => peripherals.joystick.borrow_mut().press(button),
+        InputEvent::JoystickRelease(button) => peripherals.joystick.borrow_mut().release(button),
+        InputEvent::SerialByte(byte) => peripherals.serial.borrow_mut().receive(byte),
+        InputEvent::SerialEnd => peripherals.serial.borrow_mut().end_input()
+    }
+
+    // Live key presses also drive the joystick through its key bindings, replayed scripts already contain the joystick events
+    if input_handler.script.is_none() {
+        for (bit, bound_key) in input_handler.joystick_bindings.into_iter().enumerate() {
+            match event {
+                InputEvent::KeyPress(key_code) if key_code == bound_key => apply_input_event(InputEvent::JoystickPress(1 << bit), frame, peripherals, input_handler),
+                InputEvent::KeyRelease(key_code) if key_code == bound_key => apply_input_event(InputEvent::JoystickRelease(1 << bit), frame, peripherals, input_handler),
+
+                _ => { }
+            }
+        }
+    }
+}
+
+// Runs the program until the end of the current frame, or until BRK halts it
+fn execute_code(instructions: &[Instruction], index: &mut usize, vp8: &mut Vp8System, peripherals: &Peripherals, labels: &[(String, usize)], routines: &mut Vec<usize>) -> ExecutionStatus {
+    loop {
+        match execute_instruction(instructions, index, vp8, peripherals, labels, routines) {
+            ExecutionStatus::Running => continue,
+            status => return status
+        }
+    }
+}
+
+// Runs one instruction and everything that happens before the next one: device transfers, device clocks and interrupts
+fn execute_instruction(instructions: &[Instruction], index: &mut usize, vp8: &mut Vp8System, peripherals: &Peripherals, labels: &[(String, usize)], routines: &mut Vec<usize>) -> ExecutionStatus {
+    let address: u16 = instructions[*index].value;
+    let addressing_mode: AddressingMode = instructions[*index].addressing_mode;
+    let label_name: String = instructions[*index].label_name.clone();
+    let mut cycles: u32 = instruction::cycle_count(instructions[*index].opcode, addressing_mode);
+
+    match instructions[*index].opcode {
+        Opcode::ADC => instruction::adc(address, addressing_mode, &mut vp8.registers, &mut vp8.flags, &mut vp8.memory),
+        Opcode::AND => instruction::and(address, addressing_mode, &mut vp8.registers, &mut vp8.memory),
+        Opcode::ASL => instruction::asl(address, addressing_mode, &mut vp8.registers, &mut vp8.flags, &mut vp8.memory),
+        Opcode::BIT => instruction::bit(address, addressing_mode, vp8.registers, &mut vp8.flags, &mut vp8.memory),
+        Opcode::BCC => *index = instruction::bcc(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::BCS => *index = instruction::bcs(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::BEQ => *index = instruction::beq(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::BMI => *index = instruction::bmi(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::BNE => *index = instruction::bne(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::BPL => *index = instruction::bpl(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::BVC => *index = instruction::bvc(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::BVS => *index = instruction::bvs(*index, &mut vp8.flags, label_name.clone(), labels.to_vec()),
+        Opcode::CLC => instruction::clc(&mut vp8.flags),
+        Opcode::CLD => instruction::cld(&mut vp8.flags),
+        Opcode::CLI => instruction::cli(&mut vp8.flags),
+        Opcode::CLV => instruction::clv(&mut vp8.flags),
+        Opcode::CMP => instruction::cmp(address, addressing_mode, vp8.registers, &mut vp8.flags, &mut vp8.memory),
+        Opcode::CPX => instruction::cpx(address, addressing_mode, vp8.registers, &mut vp8.flags, &mut vp8.memory),
+        Opcode::CPY => instruction::cpy(address, addressing_mode, vp8.registers, &mut vp8.flags, &mut vp8.memory),
+        Opcode::DEC => instruction::dec(address, addressing_mode, vp8.registers, &mut vp8.memory),
+        Opcode::DEX => instruction::dex(&mut vp8.registers),
+        Opcode::DEY => instruction::dey(&mut vp8.registers),
+        Opcode::EOR => instruction::eor(address, addressing_mode, &mut vp8.memory, &mut vp8.registers),
+        Opcode::INC => instruction::inc(address, addressing_mode, vp8.registers, &mut vp8.memory),
+        Opcode::INX => instruction::inx(&mut vp8.registers),
+        Opcode::INY => instruction::iny(&mut vp8.registers),
+        Opcode::JMP => *index = instruction::jmp(instructions[*index].label_name.clone(), labels.to_vec()),
+        Opcode::LDA => instruction::lda(address, addressing_mode, &mut vp8.memory, &mut vp8.registers),
+        Opcode::LDX => instruction::ldx(address, addressing_mode, &mut vp8.memory, &mut vp8.registers),
+        Opcode::LDY => instruction::ldy(address, addressing_mode, &mut vp8.memory, &mut vp8.registers),
+        Opcode::LSR => instruction::lsr(address, addressing_mode, &mut vp8.memory, &mut vp8.registers),
+        Opcode::ORA => instruction::ora(address, addressing_mode, &mut vp8.registers, &mut vp8.memory),
+        Opcode::PLA => instruction::pla(&mut vp8.registers, &mut vp8.memory),
+        Opcode::PHA => instruction::pha(vp8.registers, &mut vp8.memory),
+        Opcode::PHP => instruction::php(vp8.flags, &mut vp8.memory),
+        Opcode::PLP => instruction::plp(&mut vp8.flags, &mut vp8.memory),
+        Opcode::ROL => instruction::rol(address, addressing_mode, &mut vp8.registers, &mut vp8.memory, &mut vp8.flags),
+        Opcode::ROR => instruction::ror(address, addressing_mode, &mut vp8.registers, &mut vp8.memory, &mut vp8.flags),
+        Opcode::SBC => instruction::sbc(address, addressing_mode, &mut vp8.registers, &mut vp8.flags, &mut vp8.memory),
+        Opcode::SEC => instruction::sec(&mut vp8.flags),
+        Opcode::SED => instruction::sed(&mut vp8.flags),
+        Opcode::SEI => instruction::sei(&mut vp8.flags),
+        Opcode::STA => instruction::sta(address, addressing_mode, vp8.registers, &mut vp8.memory),
+        Opcode::STX => instruction::stx(address, addressing_mode, vp8.registers, &mut vp8.memory),
+        Opcode::STY => instruction::sty(address, addressing_mode, vp8.registers, &mut vp8.memory),
+        Opcode::TAX => instruction::tax(&mut vp8.registers),
+        Opcode::TAY => instruction::tay(&mut vp8.registers),
+        Opcode::TSX => instruction::tsx(&mut vp8.registers),
+        Opcode::TXA => instruction::txa(&mut vp8.registers),
+        Opcode::TXS => instruction::txs(&mut vp8.registers),
+        Opcode::TYA => instruction::tya(&mut vp8.registers),
+        Opcode::BRK => return ExecutionStatus::Break,
+        
+        // Kept for the programs written before the display had a refresh rate, it now waits for the next vertical blank
+        Opcode::DRW => cycles = peripherals.video.borrow().cycles_until_vblank(),
         
-        *index = increment_instruction_index!(*index, instructions.len());
-        random_number_in_memory(&mut vp8.memory);
+        Opcode::JSR => {
+            routines.push(*index);
+            *index = instruction::jmp(instructions[*index].label_name.clone(), labels.to_vec());
+        },
+
+        Opcode::RTS => {
+            if routines.is_empty() {
+                panic!("No routine ro return from, instruction {:?} at {}", instructions[*index], *index);
+            }
+
+            *index = routines.pop().unwrap();
+        },
+
+        Opcode::RTI => {
+            match vp8.interrupt_returns.pop() {
+                Some((return_index, flags)) => {
+                    *index = return_index;
+                    vp8.flags = flags;
+                },
+
+                None => panic!("No interrupt to return from, instruction {:?} at {}", instructions[*index], *index)
+            }
+        },
+
+        _ => { }
+    }
+
+    file_io::run_pending_command(&peripherals.file_io, &mut vp8.memory);
+    cycles = cycles.saturating_add(blitter::run_pending_command(&peripherals.blitter, &mut vp8.memory));
+
+    if let Some(address) = vp8.memory.take_write_fault() {
+        panic!("Instruction {:?} at {} tried to write to read-only address ${:04X}", instructions[*index], *index, address);
+    }
+
+    vp8.cycles += cycles as u64;
+    vp8.memory.tick(cycles);
+
+    // Interrupts are taken between instructions, the handler returns with RTI to the instruction after this one.
+    // The NMI cannot be masked and is only raised once per vertical blank, the IRQ stays raised until the device is acknowledged
+    if peripherals.video.borrow_mut().take_nmi() {
+        enter_interrupt_handler(NMI_HANDLER_LABEL, index, vp8, labels);
+    } else if vp8.memory.irq_pending() && !vp8.flags.get_interrupt_disable_flag() {
+        enter_interrupt_handler(IRQ_HANDLER_LABEL, index, vp8, labels);
+    }
+    
+    *index = increment_instruction_index!(*index, instructions.len());
+
+    if peripherals.video.borrow_mut().take_frame_end() {
+        sprites::detect_collisions(&peripherals.sprites, &vp8.memory);
+        return ExecutionStatus::Frame;
+    }
+
+    ExecutionStatus::Running
+}
+
+fn enter_interrupt_handler(handler_label: &str, index: &mut usize, vp8: &mut Vp8System, labels: &[(String, usize)]) {
+    if let Some((_, handler_index)) = labels.iter().find(|(label_name, _)| label_name == handler_label) {
+        vp8.interrupt_returns.push((*index, vp8.flags));
+        vp8.flags.set_interrupt_disable_flag(true);
+        *index = *handler_index;
     }
 }
 
-fn random_number_in_memory(memory: &mut system::Memory) {
-    let random_number: u8 = rand::thread_rng().gen();
-    memory.set_mem_cell_value(0xfe, random_number);
+// Places the program data and bank images in memory before the read-only regions are protected, so the loader can fill them
+fn load_memory(memory: &mut system::Memory, data_blocks: Vec<DataBlock>, config: &Config) {
+    let bank_image: Vec<u8> = match &config.bank_image {
+        Some(path) => read_file::read_binary_file(path.clone()),
+        None => vec![]
+    };
+
+    let highest_data_bank: usize = data_blocks.iter().filter_map(|block| block.bank).map(|bank| bank + 1).max().unwrap_or(0);
+    let bank_count: usize = config.banks.max((bank_image.len() + BANK_SIZE - 1) / BANK_SIZE).max(highest_data_bank);
+    let banked_memory: Rc<RefCell<BankedMemory>> = Rc::new(RefCell::new(BankedMemory::init(bank_count)));
+
+    for (bank, bank_data) in bank_image.chunks(BANK_SIZE).enumerate() {
+        banked_memory.borrow_mut().load_bank(bank, 0, bank_data);
+    }
+
+    for block in data_blocks.iter() {
+        match block.bank {
+            Some(bank) => banked_memory.borrow_mut().load_bank(bank, (block.address - system::BANK_WINDOW_START) as usize, &block.bytes),
+            None => {
+                for (offset, byte) in block.bytes.iter().enumerate() {
+                    let address: usize = block.address as usize + offset;
+
+                    // Once banks are used the window only shows banked memory, bytes placed there without a .bank go to bank 0 which is selected at start
+                    if bank_count > 0 && (system::BANK_WINDOW_START as usize..=system::BANK_WINDOW_END as usize).contains(&address) {
+                        banked_memory.borrow_mut().load_bank(0, address - system::BANK_WINDOW_START as usize, &[*byte]);
+                    } else {
+                        memory.set_mem_cell_value(address, *byte);
+                    }
+                }
+            }
+        }
+    }
+
+    if bank_count > 0 {
+        if let Some((name, start, end)) = memory.get_screen_layout().overlapped_region(true) {
+            panic!("The screen at ${:04X}-${:04X} overlaps {} at ${:04X}-${:04X}", memory.get_screen_layout().start_address, memory.get_screen_layout().end_address(), name, start, end);
+        }
+
+        memory.attach_device(system::BANK_WINDOW_START, system::BANK_WINDOW_END, Box::new(BankWindow::init(Rc::clone(&banked_memory))));
+        memory.attach_device(system::BANK_SELECT_ADDRESS, system::BANK_SELECT_ADDRESS, Box::new(BankSelect::init(banked_memory)));
+    }
+
+    for (start, end) in config.rom_regions.iter() {
+        // A ROM device holds one copy of its contents, so it cannot follow the bank select
+        if bank_count > 0 && *start <= system::BANK_WINDOW_END && *end >= system::BANK_WINDOW_START {
+            panic!("The read-only region ${:04X}-${:04X} overlaps the bank window, banked memory cannot be read-only", start, end);
+        }
+
+        memory.protect_region(*start, *end, config.rom_write_mode);
+    }
 }
 
 fn get_labels(instructions: Vec<Instruction>) -> Vec<(String, usize)> {
     let mut to_return: Vec<(String, usize)> = vec![];
 
-    for i in 0..instructions.len() {
-        if instructions[i].opcode == Opcode::LABEL {
-            to_return.push((instructions[i].label_name.clone(), i));
+    for (i, instruction) in instructions.iter().enumerate() {
+        if instruction.opcode == Opcode::LABEL {
+            to_return.push((instruction.label_name.clone(), i));
         }
     }
 
@@ -0,0 +1,75 @@
+use crate::devices::palette::Palette;
+use crate::devices::sprites::{Sprites, SPRITE_COUNT};
+use crate::devices::video::{self, Video};
+use crate::font::{CHARACTER_COUNT, GLYPH_HEIGHT};
+use crate::system::system::{self, ScreenLayout};
+use image::{Rgba, RgbaImage};
+
+// Draws what the display shows in the current display mode, with one RGBA pixel per screen pixel
+pub fn render(screen_layout: ScreenLayout, memory: &system::Memory, video: &Video, palette: &Palette, sprites: &Sprites) -> RgbaImage {
+    let mut indices: Vec<u8> = match video.display_mode() {
+        video::DISPLAY_MODE_256_COLOURS => render_bitmap(screen_layout, memory, video.scroll(), 0b11111111),
+        video::DISPLAY_MODE_TEXT => render_text(screen_layout, memory, video.scroll()),
+        _ => render_bitmap(screen_layout, memory, video.scroll(), 0b00001111)
+    };
+
+    // Sprites are drawn over the background from the last to the first, so lower numbered sprites end up on top
+    for sprite in (0..SPRITE_COUNT).rev() {
+        for (x, y, colour) in sprites.visible_pixels(sprite, memory) {
+            if x < screen_layout.width && y < screen_layout.height {
+                indices[x + y * screen_layout.width] = colour;
+            }
+        }
+    }
+
+    RgbaImage::from_fn(screen_layout.width as u32, screen_layout.height as u32, |x, y| {
+        let [red, green, blue] = palette.colour(indices[x as usize + y as usize * screen_layout.width]);
+
+        Rgba([red, green, blue, 255])
+    })
+}
+
+// The scroll offsets move the screen over the screen memory, which wraps around at its edges
+fn render_bitmap(screen_layout: ScreenLayout, memory: &system::Memory, (scroll_x, scroll_y): (usize, usize), colour_mask: u8) -> Vec<u8> {
+    let screen_memory: Vec<u8> = memory.get_screen_memory();
+    let mut to_return: Vec<u8> = Vec::with_capacity(screen_layout.size());
+
+    for y in 0..screen_layout.height {
+        let row_start: usize = (y + scroll_y) % screen_layout.height * screen_layout.width;
+
+        for x in 0..screen_layout.width {
+            to_return.push(screen_memory[row_start + (x + scroll_x) % screen_layout.width] & colour_mask);
+        }
+    }
+
+    to_return
+}
+
+// Every cell of the text map holds a character code and has a colour attribute at the same position in the colour map,
+// the low nibble of the attribute is the foreground colour and the high nibble the background colour.
+// Character codes from $80 up draw the glyph of the code without bit 7 with the colours swapped. Without scrolling the screen shows the top left corner of the map, the map wraps around at its edges
+fn render_text(screen_layout: ScreenLayout, memory: &system::Memory, (scroll_x, scroll_y): (usize, usize)) -> Vec<u8> {
+    let mut to_return: Vec<u8> = Vec::with_capacity(screen_layout.size());
+
+    for screen_y in 0..screen_layout.height {
+        for screen_x in 0..screen_layout.width {
+            let (x, y): (usize, usize) = (screen_x + scroll_x, screen_y + scroll_y);
+            let cell: usize = (y / GLYPH_HEIGHT % system::TEXT_ROWS) * system::TEXT_COLUMNS + x / 8 % system::TEXT_COLUMNS;
+            let character: usize = memory.peek_mem_cell_value(system::TEXT_MAP_START as usize + cell) as usize;
+            let attribute: u8 = memory.peek_mem_cell_value(system::TEXT_COLOUR_MAP_START as usize + cell);
+            let mut glyph_row: u8 = memory.peek_mem_cell_value(system::CHARACTER_RAM_START as usize + (character % CHARACTER_COUNT) * GLYPH_HEIGHT + y % GLYPH_HEIGHT);
+
+            if character >= CHARACTER_COUNT {
+                glyph_row = !glyph_row;
+            }
+
+            if glyph_row & (0b1000_0000 >> (x % 8)) != 0 {
+                to_return.push(attribute & 0b00001111);
+            } else {
+                to_return.push(attribute >> 4);
+            }
+        }
+    }
+
+    to_return
+}
@@ -0,0 +1,670 @@
+use crate::breakpoints::{Breakpoints, Hit, WatchKind};
+use crate::debugger::StopCondition;
+use crate::emulator::{DebugStop, DebugTarget};
+use crate::system::system::{Flags, Registers};
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+// The registers in the order of the g packet, all of them are 8 bits except the 16 bit pc, which is the number of the next instruction
+const REGISTER_COUNT: usize = 6;
+const REGISTER_PC: usize = 5;
+
+// The flags are sent as the status register of a real 6502, bit 5 is always set and B is always clear
+const FLAG_NEGATIVE: u8 = 0b1000_0000;
+const FLAG_OVERFLOW: u8 = 0b0100_0000;
+const FLAG_UNUSED: u8 = 0b0010_0000;
+const FLAG_DECIMAL: u8 = 0b0000_1000;
+const FLAG_INTERRUPT_DISABLE: u8 = 0b0000_0100;
+const FLAG_ZERO: u8 = 0b0000_0010;
+const FLAG_CARRY: u8 = 0b0000_0001;
+
+// Also the largest memory read, every byte is two characters
+const PACKET_SIZE: usize = 0x1000;
+
+const INTERRUPT: u8 = 0x03;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.vp8.cpu">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+// A stream the stub can also poll for an interrupt while the program runs
+trait Connection: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+struct GdbStub {
+    connection: Box<dyn Connection>,
+    breakpoints: Breakpoints,
+    // The Z packets name breakpoints by type, address and kind, the breakpoints by number
+    inserted: HashMap<String, usize>,
+    watch_kinds: HashMap<usize, WatchKind>,
+    last_stop: String,
+    detached: bool
+}
+
+// Waits for one debugger on a local TCP port, or on a Unix socket when the address is not a port number,
+// and serves it until it detaches, kills the program or disconnects. Returns whether the program stopped at BRK
+pub fn serve(address: &str, target: &mut DebugTarget) -> bool {
+    let connection: Box<dyn Connection> = match address.parse::<u16>() {
+        Ok(port) => {
+            let listener: TcpListener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|error| panic!("Error listening on port {port}: {error}"));
+
+            println!("Waiting for a GDB connection on 127.0.0.1:{}", listener.local_addr().map(|address| address.port()).unwrap_or(port));
+
+            let (stream, _): (TcpStream, _) = listener.accept().expect("Error accepting the GDB connection");
+            stream.set_nodelay(true).expect("Error configuring the GDB connection");
+
+            Box::new(stream)
+        }
+
+        Err(_) => accept_unix_connection(address)
+    };
+
+    let mut stub: GdbStub = GdbStub {
+        connection,
+        breakpoints: Breakpoints::init(),
+        inserted: HashMap::new(),
+        watch_kinds: HashMap::new(),
+        last_stop: "S05".to_string(),
+        detached: false
+    };
+
+    while let Some(packet) = stub.read_packet() {
+        match stub.handle_packet(&packet, target) {
+            Some(reply) => stub.send_packet(&reply),
+            None => break
+        }
+
+        if stub.detached {
+            break;
+        }
+    }
+
+    target.halted
+}
+
+#[cfg(unix)]
+fn accept_unix_connection(path: &str) -> Box<dyn Connection> {
+    let listener: UnixListener = UnixListener::bind(path).unwrap_or_else(|error| panic!("Error listening on the socket {path}: {error}"));
+
+    println!("Waiting for a GDB connection on {path}");
+
+    let (stream, _): (UnixStream, _) = listener.accept().expect("Error accepting the GDB connection");
+    let _ = std::fs::remove_file(path);
+
+    Box::new(stream)
+}
+
+#[cfg(not(unix))]
+fn accept_unix_connection(path: &str) -> Box<dyn Connection> {
+    panic!("Invalid GDB port {path}, Unix sockets are not available on this system");
+}
+
+impl GdbStub {
+    // Returns None for the packets that end the session without a reply and when the debugger disconnected while the program ran
+    fn handle_packet(&mut self, packet: &str, target: &mut DebugTarget) -> Option<String> {
+        let (command, arguments): (char, &str) = match packet.chars().next() {
+            Some(command) => (command, &packet[command.len_utf8()..]),
+            None => return Some(String::new())
+        };
+
+        let reply: String = match command {
+            '?' => self.last_stop.clone(),
+            'g' => encode_hex(&read_registers(target)),
+            'G' => match decode_hex(arguments) {
+                Some(bytes) if bytes.len() == REGISTER_COUNT + 1 => {
+                    for register in 0..REGISTER_COUNT {
+                        write_register(target, register, register_value(&bytes, register));
+                    }
+
+                    "OK".to_string()
+                }
+
+                _ => "E01".to_string()
+            },
+            'p' => match usize::from_str_radix(arguments, 16) {
+                Ok(register) if register < REGISTER_COUNT => encode_hex(register_bytes(&read_registers(target), register)),
+                _ => "E01".to_string()
+            },
+            'P' => match arguments.split_once('=').map(|(register, value)| (usize::from_str_radix(register, 16), decode_hex(value))) {
+                Some((Ok(register), Some(bytes))) if register < REGISTER_COUNT && !bytes.is_empty() => {
+                    let value: u16 = if bytes.len() > 1 { u16::from_le_bytes([bytes[0], bytes[1]]) } else { bytes[0] as u16 };
+
+                    write_register(target, register, value);
+                    "OK".to_string()
+                }
+
+                _ => "E01".to_string()
+            },
+            'm' => match parse_address_and_length(arguments) {
+                Some((address, length)) => {
+                    let bytes: Vec<u8> = (0..length.min(PACKET_SIZE / 2))
+                        .map(|offset| target.vp8.memory.peek_mem_cell_value((address + offset) & 0xffff))
+                        .collect();
+
+                    encode_hex(&bytes)
+                }
+
+                None => "E01".to_string()
+            },
+            'M' => match arguments.split_once(':').and_then(|(range, data)| Some((parse_address_and_length(range)?, decode_hex(data)?))) {
+                Some(((address, length), bytes)) if bytes.len() == length => {
+                    let mut faulted: bool = false;
+
+                    for (offset, byte) in bytes.iter().enumerate() {
+                        target.vp8.memory.set_mem_cell_value((address + offset) & 0xffff, *byte);
+                        faulted |= target.vp8.memory.take_write_fault().is_some();
+                    }
+
+                    if faulted { "E02".to_string() } else { "OK".to_string() }
+                }
+
+                _ => "E01".to_string()
+            },
+            'c' | 's' => {
+                if let Ok(index) = usize::from_str_radix(arguments, 16) {
+                    if index < target.instructions().len() {
+                        target.index = index;
+                    }
+                }
+
+                let mut stop_condition: StopCondition = if command == 's' { StopCondition::Steps(1) } else { StopCondition::Never };
+
+                self.run(target, &mut stop_condition)?
+            },
+            'Z' | 'z' => self.update_breakpoint(command == 'Z', arguments, target),
+            'H' | 'T' => "OK".to_string(),
+            'D' => {
+                self.detached = true;
+                "OK".to_string()
+            },
+            'k' => return None,
+            'q' => query(packet),
+
+            _ => String::new()
+        };
+
+        Some(reply)
+    }
+
+    // Returns the stop reply, or None when the debugger closed the connection or it failed, which also stops the program
+    fn run(&mut self, target: &mut DebugTarget, stop_condition: &mut StopCondition) -> Option<String> {
+        let connection: &mut Box<dyn Connection> = &mut self.connection;
+        let mut disconnected: bool = false;
+
+        let stop: DebugStop = target.run(stop_condition, &mut self.breakpoints, &mut || {
+            let mut byte: [u8; 1] = [0];
+
+            let _ = connection.set_nonblocking(true);
+            let interrupted: bool = match connection.read(&mut byte) {
+                Ok(1) => byte[0] == INTERRUPT,
+                Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => false,
+
+                _ => {
+                    disconnected = true;
+                    true
+                }
+            };
+            let _ = connection.set_nonblocking(false);
+
+            interrupted
+        });
+
+        if disconnected {
+            return None;
+        }
+
+        self.last_stop = match stop {
+            DebugStop::Halted => "W00".to_string(),
+            DebugStop::Interrupted => "S02".to_string(),
+
+            DebugStop::Hits(hits) => {
+                let watch_hit: Option<String> = hits.iter().find_map(|hit| match hit {
+                    Hit::Watchpoint { id, access, .. } => {
+                        let name: &str = match self.watch_kinds.get(id) {
+                            Some(WatchKind::Read) => "rwatch",
+                            Some(WatchKind::Access) => "awatch",
+                            _ => "watch"
+                        };
+
+                        Some(format!("T05{name}:{:x};", access.address))
+                    }
+
+                    Hit::Breakpoint { .. } => None
+                });
+
+                watch_hit.unwrap_or_else(|| "S05".to_string())
+            }
+
+            DebugStop::Reached | DebugStop::CycleLimit => "S05".to_string()
+        };
+
+        Some(self.last_stop.clone())
+    }
+
+    // Types 0 and 1 are breakpoints on an instruction number, 2, 3 and 4 are write, read and access watchpoints on a memory range
+    fn update_breakpoint(&mut self, insert: bool, arguments: &str, target: &DebugTarget) -> String {
+        let parts: Vec<&str> = arguments.split(';').next().unwrap_or("").split(',').collect();
+
+        let (point_type, address, length): (&str, usize, usize) = match parts.as_slice() {
+            [point_type, address, length] => match (usize::from_str_radix(address, 16), usize::from_str_radix(length, 16)) {
+                (Ok(address), Ok(length)) => (point_type, address, length),
+                _ => return "E01".to_string()
+            },
+
+            _ => return "E01".to_string()
+        };
+
+        let key: String = format!("{point_type},{address:x},{length:x}");
+
+        if !insert {
+            if let Some(id) = self.inserted.remove(&key) {
+                self.breakpoints.delete(id);
+                self.watch_kinds.remove(&id);
+            }
+
+            return "OK".to_string();
+        }
+
+        if self.inserted.contains_key(&key) {
+            return "OK".to_string();
+        }
+
+        let id: usize = match point_type {
+            "0" | "1" if address < target.instructions().len() => self.breakpoints.add_breakpoint(address, None, 0, false),
+
+            "2" | "3" | "4" if address <= 0xffff => {
+                let kind: WatchKind = match point_type {
+                    "2" => WatchKind::Write,
+                    "3" => WatchKind::Read,
+                    _ => WatchKind::Access
+                };
+
+                let end: u16 = address.saturating_add(length.max(1) - 1).min(0xffff) as u16;
+                let id: usize = self.breakpoints.add_watchpoint(address as u16, end, kind, None);
+
+                self.watch_kinds.insert(id, kind);
+                id
+            }
+
+            "0" | "1" | "2" | "3" | "4" => return "E01".to_string(),
+            _ => return String::new()
+        };
+
+        self.inserted.insert(key, id);
+        "OK".to_string()
+    }
+
+    // Skips acknowledgements and interrupts sent while the program was already stopped, a packet with a bad checksum is asked for again.
+    // Returns None once the connection is closed
+    fn read_packet(&mut self) -> Option<String> {
+        loop {
+            if self.read_byte()? != b'$' {
+                continue;
+            }
+
+            let mut data: Vec<u8> = vec![];
+
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    byte => data.push(byte)
+                }
+            }
+
+            let checksum: String = String::from_utf8_lossy(&[self.read_byte()?, self.read_byte()?]).to_string();
+
+            if u8::from_str_radix(&checksum, 16).ok() == Some(packet_checksum(&data)) {
+                self.write_bytes(b"+");
+                return Some(String::from_utf8_lossy(&data).to_string());
+            }
+
+            self.write_bytes(b"-");
+        }
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut byte: [u8; 1] = [0];
+
+        loop {
+            match self.connection.read(&mut byte) {
+                Ok(1) => return Some(byte[0]),
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+
+                _ => return None
+            }
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) {
+        self.write_bytes(format!("${data}#{:02x}", packet_checksum(data.as_bytes())).as_bytes());
+    }
+
+    // A debugger that went away is noticed by the next read
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let _ = self.connection.write_all(bytes).and_then(|_| self.connection.flush());
+    }
+}
+
+fn query(packet: &str) -> String {
+    if packet.starts_with("qSupported") {
+        return format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+");
+    }
+
+    if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        return match parse_address_and_length(range) {
+            Some((offset, _)) if offset >= TARGET_XML.len() => "l".to_string(),
+            Some((offset, length)) if length >= TARGET_XML.len() - offset => format!("l{}", &TARGET_XML[offset..]),
+            Some((offset, length)) => format!("m{}", &TARGET_XML[offset..offset + length]),
+
+            None => "E01".to_string()
+        };
+    }
+
+    match packet {
+        "qAttached" => "1".to_string(),
+        "qC" => "QC1".to_string(),
+        "qfThreadInfo" => "m1".to_string(),
+        "qsThreadInfo" => "l".to_string(),
+
+        _ => String::new()
+    }
+}
+
+fn read_registers(target: &DebugTarget) -> Vec<u8> {
+    let registers: Registers = target.vp8.registers;
+    let flags: Flags = target.vp8.flags;
+    let mut status: u8 = FLAG_UNUSED;
+
+    for (set, bit) in [(flags.get_negative_flag(), FLAG_NEGATIVE), (flags.get_overflow_flag(), FLAG_OVERFLOW), (flags.get_decimal_flag(), FLAG_DECIMAL),
+        (flags.get_interrupt_disable_flag(), FLAG_INTERRUPT_DISABLE), (flags.get_zerro_flag(), FLAG_ZERO), (flags.get_carry_flag(), FLAG_CARRY)] {
+        if set {
+            status |= bit;
+        }
+    }
+
+    let pc: [u8; 2] = (target.index as u16).to_le_bytes();
+
+    vec![registers.get_acc(), registers.get_x(), registers.get_y(), registers.get_sp(), status, pc[0], pc[1]]
+}
+
+// Every register is one byte of the g packet, the pc is the last two
+fn register_bytes(bytes: &[u8], register: usize) -> &[u8] {
+    if register == REGISTER_PC { &bytes[REGISTER_PC..] } else { &bytes[register..register + 1] }
+}
+
+fn register_value(bytes: &[u8], register: usize) -> u16 {
+    if register == REGISTER_PC { u16::from_le_bytes([bytes[REGISTER_PC], bytes[REGISTER_PC + 1]]) } else { bytes[register] as u16 }
+}
+
+fn write_register(target: &mut DebugTarget, register: usize, value: u16) {
+    let byte: u8 = value as u8;
+
+    match register {
+        0 => target.vp8.registers.set_acc(byte),
+        1 => target.vp8.registers.set_x(byte),
+        2 => target.vp8.registers.set_y(byte),
+        3 => target.vp8.registers.set_sp(byte),
+        4 => {
+            target.vp8.flags.set_negative_flag(byte & FLAG_NEGATIVE != 0);
+            target.vp8.flags.set_overflow_flag(byte & FLAG_OVERFLOW != 0);
+            target.vp8.flags.set_decimal_flag(byte & FLAG_DECIMAL != 0);
+            target.vp8.flags.set_interrupt_disable_flag(byte & FLAG_INTERRUPT_DISABLE != 0);
+            target.vp8.flags.set_zerro_flag(byte & FLAG_ZERO != 0);
+            target.vp8.flags.set_carry_flag(byte & FLAG_CARRY != 0);
+        }
+
+        // The program only has so many instructions, a pc past the end is ignored
+        _ => {
+            if (value as usize) < target.instructions().len() {
+                target.index = value as usize;
+            }
+        }
+    }
+}
+
+// Addresses past the 64 kilobyte address space are malformed, the length is left to the packet to limit
+fn parse_address_and_length(text: &str) -> Option<(usize, usize)> {
+    let (address, length): (&str, &str) = text.split_once(',')?;
+    let address: usize = usize::from_str_radix(address, 16).ok().filter(|address| *address <= 0xffff)?;
+
+    Some((address, usize::from_str_radix(length, 16).ok()?))
+}
+
+fn packet_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, byte| sum.wrapping_add(*byte))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..text.len()).step_by(2).map(|position| u8::from_str_radix(text.get(position..position + 2)?, 16).ok()).collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::analyze_code;
+    use crate::config::Config;
+    use crate::emulator::{self, FinalState};
+    use std::path::PathBuf;
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    // Counts X up to 3 storing it at $0300, the instructions are LDX (0), the label (1), INX (2), STX (3), CPX (4), BNE (5) and BRK (6)
+    const COUNTING_PROGRAM: [&str; 7] = ["LDX #$00", "count:", "INX", "STX $0300", "CPX #$03", "BNE count", "BRK"];
+    const SPINNING_PROGRAM: [&str; 2] = ["spin:", "JMP spin"];
+
+    // The debugger side of a session, it checks the acknowledgements and leaves the replies to the test
+    struct Client {
+        stream: UnixStream
+    }
+
+    impl Client {
+        fn connect(path: &PathBuf) -> Client {
+            for _ in 0..500 {
+                if let Ok(stream) = UnixStream::connect(path) {
+                    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+                    return Client { stream };
+                }
+
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            panic!("The stub never listened on {}", path.display());
+        }
+
+        fn send(&mut self, data: &str) {
+            self.stream.write_all(format!("${data}#{:02x}", packet_checksum(data.as_bytes())).as_bytes()).unwrap();
+        }
+
+        fn read_byte(&mut self) -> Option<u8> {
+            let mut byte: [u8; 1] = [0];
+
+            match self.stream.read(&mut byte) {
+                Ok(1) => Some(byte[0]),
+                _ => None
+            }
+        }
+
+        fn read_reply(&mut self) -> String {
+            assert_eq!(self.read_byte(), Some(b'$'));
+
+            let mut data: Vec<u8> = vec![];
+
+            loop {
+                match self.read_byte().expect("The stub closed the connection in a reply") {
+                    b'#' => break,
+                    byte => data.push(byte)
+                }
+            }
+
+            let checksum: [u8; 2] = [self.read_byte().unwrap(), self.read_byte().unwrap()];
+
+            assert_eq!(u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).ok(), Some(packet_checksum(&data)));
+            String::from_utf8(data).unwrap()
+        }
+
+        fn request(&mut self, data: &str) -> String {
+            self.send(data);
+            assert_eq!(self.read_byte(), Some(b'+'));
+
+            self.read_reply()
+        }
+
+        fn kill(&mut self) {
+            self.send("k");
+            assert_eq!(self.read_byte(), Some(b'+'));
+            assert_eq!(self.read_byte(), None);
+        }
+    }
+
+    // Runs the program under the stub on a Unix socket and the session on its own thread, the emulator stays on this one
+    fn run_session(name: &str, program: &[&str], session: impl FnOnce(&mut Client) + Send + 'static) -> FinalState {
+        let path: PathBuf = std::env::temp_dir().join(format!("vp8-gdb-{}-{name}.sock", std::process::id()));
+        let lines: Vec<String> = program.iter().map(|line| line.to_string()).collect();
+        let mut config: Config = Config::init();
+
+        let _ = std::fs::remove_file(&path);
+        config.gdb = Some(path.to_string_lossy().to_string());
+        config.seed = Some(0);
+
+        let client_path: PathBuf = path.clone();
+        let client: JoinHandle<()> = thread::spawn(move || session(&mut Client::connect(&client_path)));
+        let final_state: FinalState = emulator::start_emulator(analyze_code::get_instructions(lines.clone()), analyze_code::get_data_blocks(lines), config);
+
+        client.join().expect("The session failed");
+        final_state
+    }
+
+    #[test]
+    fn registers_and_memory_are_read_and_written() {
+        run_session("registers", &COUNTING_PROGRAM, |client| {
+            assert_eq!(client.request("qSupported:multiprocess+"), "PacketSize=1000;qXfer:features:read+");
+            assert!(client.request("qXfer:features:read:target.xml:0,1000").starts_with("l<?xml"));
+
+            assert_eq!(client.request("g"), "000000ff200000");
+            assert_eq!(client.request("G010203fe210200"), "OK");
+            assert_eq!(client.request("g"), "010203fe210200");
+            assert_eq!(client.request("p1"), "02");
+            assert_eq!(client.request("P0=7f"), "OK");
+            assert_eq!(client.request("p0"), "7f");
+            assert_eq!(client.request("P5=0300"), "OK");
+            assert_eq!(client.request("p5"), "0300");
+            assert_eq!(client.request("p6"), "E01");
+
+            assert_eq!(client.request("M4300,3:abcdef"), "OK");
+            assert_eq!(client.request("m4300,3"), "abcdef");
+            assert_eq!(client.request("M4300,2:ab"), "E01");
+
+            client.kill();
+        });
+    }
+
+    #[test]
+    fn breakpoints_watchpoints_and_steps_stop_the_program() {
+        let final_state: FinalState = run_session("execution", &COUNTING_PROGRAM, |client| {
+            // The label after LDX is not a step of its own
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(client.request("p5"), "0200");
+
+            assert_eq!(client.request("Z0,3,1"), "OK");
+            assert_eq!(client.request("c"), "S05");
+            assert_eq!(client.request("g"), "000100ff200300");
+            assert_eq!(client.request("z0,3,1"), "OK");
+
+            assert_eq!(client.request("Z2,300,1"), "OK");
+            assert_eq!(client.request("c"), "T05watch:300;");
+            assert_eq!(client.request("m300,1"), "01");
+            assert_eq!(client.request("z2,300,1"), "OK");
+
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(client.request("p5"), "0500");
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(client.request("p5"), "0200");
+
+            assert_eq!(client.request("c"), "W00");
+            assert_eq!(client.request("?"), "W00");
+
+            client.kill();
+        });
+
+        assert!(final_state.halted);
+        assert_eq!(final_state.registers.get_x(), 3);
+    }
+
+    #[test]
+    fn interrupts_and_bad_checksums_are_answered() {
+        let final_state: FinalState = run_session("interrupt", &SPINNING_PROGRAM, |client| {
+            client.stream.write_all(b"$g#00").unwrap();
+            assert_eq!(client.read_byte(), Some(b'-'));
+
+            client.send("c");
+            assert_eq!(client.read_byte(), Some(b'+'));
+            client.stream.write_all(&[INTERRUPT]).unwrap();
+            assert_eq!(client.read_reply(), "S02");
+
+            client.kill();
+        });
+
+        assert!(!final_state.halted);
+    }
+
+    #[test]
+    fn closing_the_connection_while_running_ends_the_session() {
+        let final_state: FinalState = run_session("disconnect", &SPINNING_PROGRAM, |client| {
+            client.send("c");
+            assert_eq!(client.read_byte(), Some(b'+'));
+        });
+
+        // Returning at all means the stub stopped the spinning program once the client was gone
+        assert!(!final_state.halted);
+    }
+
+    #[test]
+    fn out_of_range_addresses_and_lengths_are_rejected() {
+        run_session("ranges", &COUNTING_PROGRAM, |client| {
+            assert_eq!(client.request("mffffffffffffffff,2"), "E01");
+            assert_eq!(client.request("m10000,1"), "E01");
+            assert_eq!(client.request("Mffffffffffffffff,1:00"), "E01");
+            assert_eq!(client.request("mffff,ffffffffffffffff").len(), PACKET_SIZE);
+            assert_eq!(client.request("Z2,ffff,ffffffffffffffff"), "OK");
+            assert_eq!(client.request("Z2,10000,1"), "E01");
+            assert!(client.request("qXfer:features:read:target.xml:10,ffffffffffffffff").starts_with('l'));
+
+            client.kill();
+        });
+    }
+}
@@ -0,0 +1,144 @@
+use crate::devices::{joystick, keyboard, mouse};
+use crate::read_file;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyPress(u8),
+    KeyRelease(u8),
+    MouseMove(u8, u8),
+    MousePress(u8),
+    MouseRelease(u8),
+    JoystickPress(u8),
+    JoystickRelease(u8),
+    SerialByte(u8),
+    SerialEnd
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptedEvent {
+    pub frame: u64,
+    pub event: InputEvent
+}
+
+#[derive(Debug, Clone)]
+pub struct InputScript {
+    pub seed: Option<u64>,
+    pub events: Vec<ScriptedEvent>
+}
+
+// Writes every input event the emulator applies in the input script format, together with the random seed,
+// so replaying the file with --input-script reproduces the run exactly
+pub struct InputRecorder {
+    writer: BufWriter<File>
+}
+
+impl InputRecorder {
+    pub fn create(path: String, seed: u64) -> InputRecorder {
+        let mut writer: BufWriter<File> = BufWriter::new(File::create(path).expect("Error creating input log"));
+
+        writeln!(writer, "seed {seed}").expect("Error writing input log");
+
+        InputRecorder { writer }
+    }
+
+    pub fn record(&mut self, frame: u64, event: InputEvent) {
+        writeln!(self.writer, "{frame} {}", format_event(event)).expect("Error writing input log");
+    }
+}
+
+impl Drop for InputRecorder {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+// Every line of an input script is `frame action arguments`, for example `10 press space`, `12 release a`, `14 mouse-move 64 32`,
+// `15 mouse-press left`, `16 joystick-press start`, `17 serial $41` or `18 serial-end`, events are applied before the given frame runs and lines starting with ; are comments.
+// A `seed N` line sets the seed of the random number generator
+pub fn read_input_script(path: String) -> InputScript {
+    let mut to_return: InputScript = InputScript { seed: None, events: vec![] };
+
+    for line in read_file::read_file(path) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        if parts.len() < 2 {
+            panic!("Invalid line in input script: {line}");
+        }
+
+        if parts[0] == "seed" {
+            to_return.seed = Some(parts[1].parse().unwrap_or_else(|_| panic!("Invalid seed in input script line: {line}")));
+            continue;
+        }
+
+        let frame: u64 = parts[0].parse().unwrap_or_else(|_| panic!("Invalid frame number in input script line: {line}"));
+        let event: InputEvent = match (parts[1], parts.get(2)) {
+            ("press", Some(key)) => InputEvent::KeyPress(get_key_code(key, &line)),
+            ("release", Some(key)) => InputEvent::KeyRelease(get_key_code(key, &line)),
+            ("mouse-move", Some(x)) if parts.len() == 4 => InputEvent::MouseMove(get_coordinate(x, &line), get_coordinate(parts[3], &line)),
+            ("mouse-press", Some(button)) => InputEvent::MousePress(get_button(button, &line)),
+            ("mouse-release", Some(button)) => InputEvent::MouseRelease(get_button(button, &line)),
+            ("joystick-press", Some(button)) => InputEvent::JoystickPress(get_joystick_button(button, &line)),
+            ("joystick-release", Some(button)) => InputEvent::JoystickRelease(get_joystick_button(button, &line)),
+            ("serial", Some(byte)) => InputEvent::SerialByte(get_serial_byte(byte, &line)),
+            ("serial-end", None) => InputEvent::SerialEnd,
+
+            _ => panic!("Invalid action in input script line: {line}")
+        };
+
+        to_return.events.push(ScriptedEvent { frame, event });
+    }
+
+    to_return.events.sort_by_key(|scripted_event| scripted_event.frame);
+    to_return
+}
+
+fn format_event(event: InputEvent) -> String {
+    match event {
+        InputEvent::KeyPress(key_code) => format!("press {}", keyboard::key_name(key_code)),
+        InputEvent::KeyRelease(key_code) => format!("release {}", keyboard::key_name(key_code)),
+        InputEvent::MouseMove(x, y) => format!("mouse-move {x} {y}"),
+        InputEvent::MousePress(button) => format!("mouse-press {}", mouse::button_name(button)),
+        InputEvent::MouseRelease(button) => format!("mouse-release {}", mouse::button_name(button)),
+        InputEvent::JoystickPress(button) => format!("joystick-press {}", joystick::button_name(button)),
+        InputEvent::JoystickRelease(button) => format!("joystick-release {}", joystick::button_name(button)),
+        InputEvent::SerialByte(byte) => format!("serial ${byte:02X}"),
+        InputEvent::SerialEnd => "serial-end".to_string()
+    }
+}
+
+fn get_key_code(name: &str, line: &str) -> u8 {
+    match keyboard::key_code_from_name(name) {
+        Some(key_code) => key_code,
+        None => panic!("Unknown key {name} in input script line: {line}")
+    }
+}
+
+fn get_button(name: &str, line: &str) -> u8 {
+    match mouse::button_from_name(name) {
+        Some(button) => button,
+        None => panic!("Unknown mouse button {name} in input script line: {line}")
+    }
+}
+
+fn get_joystick_button(name: &str, line: &str) -> u8 {
+    match joystick::button_from_name(name) {
+        Some(button) => button,
+        None => panic!("Unknown joystick button {name} in input script line: {line}")
+    }
+}
+
+fn get_coordinate(value: &str, line: &str) -> u8 {
+    match value.parse::<u8>() {
+        Ok(coordinate) => coordinate,
+        _ => panic!("Invalid screen coordinate {value} in input script line: {line}")
+    }
+}
+
+fn get_serial_byte(value: &str, line: &str) -> u8 {
+    match u8::from_str_radix(value.trim_start_matches('$'), 16) {
+        Ok(byte) => byte,
+        _ => panic!("Invalid serial byte {value} in input script line: {line}")
+    }
+}
@@ -1,6 +1,6 @@
 use std::cmp::Ordering;
 
-use crate::analyze_code::AddressingMode;
+use crate::analyze_code::{AddressingMode, Opcode};
 use crate::system::system;
 
 macro_rules! check_flag_status {
@@ -13,19 +13,8 @@ macro_rules! check_flag_status {
     };
 }
 
-pub fn adc(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, flags: &mut system::Flags, memory: system::Memory) {
-    let mut result: u16 = registers.get_acc() as u16;
-    
-    match addressing_mode {
-        AddressingMode::Immediate => result += address, 
-        AddressingMode::ZeroPage | AddressingMode::Absolute => result += memory.get_mem_cell_value(address as usize) as u16,
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => result += memory.get_mem_cell_value(address as usize + registers.get_x() as usize) as u16,
-        AddressingMode::AbsoluteY => result += memory.get_mem_cell_value(address as usize + registers.get_y() as usize) as u16,
-        AddressingMode::IndirectX => result += memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_x())) as u16,
-        AddressingMode::IndirectY => result += memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_y())) as u16,
-
-        _ => { }
-    }
+pub fn adc(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, flags: &mut system::Flags, memory: &mut system::Memory) {
+    let result: u16 = registers.get_acc() as u16 + read_operand(address, addressing_mode, *registers, memory) as u16;
 
     if result > 255 {
         flags.set_carry_flag(true);
@@ -34,56 +23,21 @@ pub fn adc(address: u16, addressing_mode: AddressingMode, registers: &mut system
     registers.set_acc(result as u8);
 }
 
-pub fn and(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: system::Memory) {
-    let mut result: u8 = registers.get_acc();
-    
-    match addressing_mode {
-        AddressingMode::Immediate => result &= address as u8, 
-        AddressingMode::ZeroPage | AddressingMode::Absolute => result &= memory.get_mem_cell_value(address as usize),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => result &= memory.get_mem_cell_value(address as usize + registers.get_x() as usize),
-        AddressingMode::AbsoluteY => result &= memory.get_mem_cell_value(address as usize + registers.get_y() as usize),
-        AddressingMode::IndirectX => result &= memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_x())),
-        AddressingMode::IndirectY => result &= memory.get_mem_cell_value(indirect_indexed_address(memory, address, registers.get_y())),
-
-        _ => { }
-    }
-
-    registers.set_acc(result);
+pub fn and(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: &mut system::Memory) {
+    registers.set_acc(registers.get_acc() & read_operand(address, addressing_mode, *registers, memory));
 }
 
 pub fn asl(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, _flags: &mut system::Flags, memory: &mut system::Memory) {
-    let mut shifted_value: u8 = 0;
-    
-    match addressing_mode {
-        AddressingMode::Implied => shifted_value = registers.get_acc() << 1,
-        AddressingMode::ZeroPage | AddressingMode::Absolute => shifted_value = memory.get_mem_cell_value(address as usize) << 1,
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => shifted_value = memory.get_mem_cell_value(address as usize + registers.get_x() as usize) << 1,
+    let shifted_value: u8 = read_operand(address, addressing_mode, *registers, memory) << 1;
 
-        _ => { }
-    }
-
-    if addressing_mode == AddressingMode::Implied {
-        registers.set_acc(shifted_value);
-    } else if addressing_mode == AddressingMode::ZeroPage || addressing_mode == AddressingMode::Absolute {
-        memory.set_mem_cell_value(address as usize, shifted_value);
-    } else {
-        memory.set_mem_cell_value(address as usize + registers.get_x() as usize, shifted_value);
-    }
+    write_operand(address, addressing_mode, registers, memory, shifted_value);
 }
 
-pub fn bit(address: u16, addressing_mode: AddressingMode, flags: &mut system::Flags, memory: system::Memory) {
-    match addressing_mode {
-        AddressingMode::ZeroPage | AddressingMode::Absolute => {
-            let mem_cell_data: u8 = memory.get_mem_cell_value(address as usize);
-            let new_n_flag_value: bool = (mem_cell_data & 128) == 1;
-            let new_v_flag_value: bool = (mem_cell_data & 64) == 1;
-
-            flags.set_negative_flag(new_n_flag_value);
-            flags.set_overflow_flag(new_v_flag_value);
-        }
+pub fn bit(address: u16, addressing_mode: AddressingMode, registers: system::Registers, flags: &mut system::Flags, memory: &mut system::Memory) {
+    let mem_cell_data: u8 = read_operand(address, addressing_mode, registers, memory);
 
-        _ => { }
-    }
+    flags.set_negative_flag((mem_cell_data & 128) != 0);
+    flags.set_overflow_flag((mem_cell_data & 64) != 0);
 }
 
 pub fn bpl(current_index: usize, flags: &mut system::Flags, label_name: String, labels: Vec<(String, usize)>) -> usize {
@@ -147,32 +101,20 @@ pub fn sed(flags: &mut system::Flags) {
 }
 
 pub fn sta(address: u16, addressing_mode: AddressingMode, registers: system::Registers, memory: &mut system::Memory) {
-    match addressing_mode {
-        AddressingMode::ZeroPage | AddressingMode::Absolute => memory.set_mem_cell_value(address as usize, registers.get_acc()),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => memory.set_mem_cell_value(address as usize, registers.get_acc()),
-        AddressingMode::AbsoluteY => memory.set_mem_cell_value(address as usize + registers.get_y() as usize, registers.get_acc()),
-        AddressingMode::IndirectX => memory.set_mem_cell_value(indexed_indirect_address(*memory, address, registers.get_x()), registers.get_acc()),
-        AddressingMode::IndirectY => memory.set_mem_cell_value(indirect_indexed_address(*memory, address, registers.get_y()), registers.get_acc()),
-        
-        _ => { }
+    if let Some(target_address) = effective_address(address, addressing_mode, registers, memory) {
+        memory.set_mem_cell_value(target_address, registers.get_acc());
     }
 }
 
 pub fn stx(address: u16, addressing_mode: AddressingMode, registers: system::Registers, memory: &mut system::Memory) {
-    match addressing_mode {
-        AddressingMode::ZeroPage | AddressingMode::Absolute => memory.set_mem_cell_value(address as usize, registers.get_x()),
-        AddressingMode::ZeroPageY => memory.set_mem_cell_value(address as usize + registers.get_y() as usize, registers.get_x()),
-
-        _ => { }
+    if let Some(target_address) = effective_address(address, addressing_mode, registers, memory) {
+        memory.set_mem_cell_value(target_address, registers.get_x());
     }
 }
 
 pub fn sty(address: u16, addressing_mode: AddressingMode, registers: system::Registers, memory: &mut system::Memory) {
-    match addressing_mode {
-        AddressingMode::ZeroPage | AddressingMode::Absolute => memory.set_mem_cell_value(address as usize, registers.get_y()),
-        AddressingMode::ZeroPageX => memory.set_mem_cell_value(address as usize + registers.get_x() as usize, registers.get_y()),
-
-        _ => { }
+    if let Some(target_address) = effective_address(address, addressing_mode, registers, memory) {
+        memory.set_mem_cell_value(target_address, registers.get_y());
     }
 }
 
@@ -200,42 +142,23 @@ pub fn tya(registers: &mut system::Registers) {
     registers.set_acc(registers.get_y());
 }
 
-pub fn cmp(address: u16, addressing_mode: AddressingMode, registers: system::Registers, flags: &mut system::Flags, memory: system::Memory) {
-    match addressing_mode {
-        AddressingMode::Immediate => compare(registers.get_acc(), address as u8, flags),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => compare(registers.get_acc(), memory.get_mem_cell_value(address as usize), flags),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => compare(registers.get_acc(), memory.get_mem_cell_value(address as usize + registers.get_x() as usize), flags),
-        AddressingMode::AbsoluteY => compare(registers.get_acc(), memory.get_mem_cell_value(address as usize + registers.get_y() as usize), flags),
-        AddressingMode::IndirectX => compare(registers.get_acc(), memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_x())), flags),
-        AddressingMode::IndirectY => compare(registers.get_acc(), memory.get_mem_cell_value(indirect_indexed_address(memory, address, registers.get_y())), flags),
-
-        _ => { }
-    }
+pub fn cmp(address: u16, addressing_mode: AddressingMode, registers: system::Registers, flags: &mut system::Flags, memory: &mut system::Memory) {
+    compare(registers.get_acc(), read_operand(address, addressing_mode, registers, memory), flags);
 }
 
-pub fn cpx(address: u16, addressing_mode: AddressingMode, registers: system::Registers, flags: &mut system::Flags, memory: system::Memory) {
-    match addressing_mode {
-        AddressingMode::Immediate => compare(registers.get_x(), address as u8, flags),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => compare(registers.get_x(), memory.get_mem_cell_value(address as usize), flags),
-        _ => { }
-    }
+pub fn cpx(address: u16, addressing_mode: AddressingMode, registers: system::Registers, flags: &mut system::Flags, memory: &mut system::Memory) {
+    compare(registers.get_x(), read_operand(address, addressing_mode, registers, memory), flags);
 }
 
-pub fn cpy(address: u16, addressing_mode: AddressingMode, registers: system::Registers, flags: &mut system::Flags, memory: system::Memory) {
-    match addressing_mode {
-        AddressingMode::Immediate => compare(registers.get_y(), address as u8, flags),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => compare(registers.get_y(), memory.get_mem_cell_value(address as usize), flags),
-        
-        _ => { }
-    } 
+pub fn cpy(address: u16, addressing_mode: AddressingMode, registers: system::Registers, flags: &mut system::Flags, memory: &mut system::Memory) {
+    compare(registers.get_y(), read_operand(address, addressing_mode, registers, memory), flags);
 }
 
 pub fn dec(address: u16, addressing_mode: AddressingMode, registers: system::Registers, memory: &mut system::Memory) {
-    match addressing_mode {
-        AddressingMode::ZeroPage | AddressingMode::Absolute => memory.set_mem_cell_value(address as usize, memory.get_mem_cell_value(address as usize) - 1),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => memory.set_mem_cell_value(address as usize + registers.get_x() as usize, memory.get_mem_cell_value(address as usize) - 1),
+    if let Some(target_address) = effective_address(address, addressing_mode, registers, memory) {
+        let decremented_value: u8 = memory.get_mem_cell_value(target_address).wrapping_sub(1);
 
-        _ => { }
+        memory.set_mem_cell_value(target_address, decremented_value);
     }
 }
 
@@ -256,11 +179,10 @@ pub fn dey(registers: &mut system::Registers) {
 }
 
 pub fn inc(address: u16, addressing_mode: AddressingMode, registers: system::Registers, memory: &mut system::Memory) {
-    match addressing_mode {
-        AddressingMode::ZeroPage | AddressingMode::Absolute => memory.set_mem_cell_value(address as usize, memory.get_mem_cell_value(address as usize) + 1),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => memory.set_mem_cell_value(address as usize + registers.get_x() as usize, memory.get_mem_cell_value(address as usize) + 1),
+    if let Some(target_address) = effective_address(address, addressing_mode, registers, memory) {
+        let incremented_value: u8 = memory.get_mem_cell_value(target_address).wrapping_add(1);
 
-        _ => { }
+        memory.set_mem_cell_value(target_address, incremented_value);
     }
 }
 
@@ -280,49 +202,20 @@ pub fn iny(registers: &mut system::Registers) {
     }
 }
 
-pub fn eor(address: u16, addressing_mode: AddressingMode, memory: system::Memory, registers: &mut system::Registers) {
-    match addressing_mode {
-        AddressingMode::ZeroPage | AddressingMode::Absolute => registers.set_acc(registers.get_acc() ^ memory.get_mem_cell_value(address as usize)),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => registers.set_acc(registers.get_acc() ^ memory.get_mem_cell_value(address as usize + registers.get_acc() as usize)),
-        AddressingMode::AbsoluteY => registers.set_acc(registers.get_acc() ^ memory.get_mem_cell_value(address as usize + registers.get_y() as usize)),
-        AddressingMode::IndirectX => registers.set_acc(registers.get_acc() ^ memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_x()))),
-        AddressingMode::IndirectY => registers.set_acc(registers.get_acc() ^ memory.get_mem_cell_value(indirect_indexed_address(memory, address, registers.get_y()))),
-
-        _ => { }
-    }
+pub fn eor(address: u16, addressing_mode: AddressingMode, memory: &mut system::Memory, registers: &mut system::Registers) {
+    registers.set_acc(registers.get_acc() ^ read_operand(address, addressing_mode, *registers, memory));
 }
 
-pub fn lda(address: u16, addressing_mode: AddressingMode, memory: system::Memory, registers: &mut system::Registers) {
-    match addressing_mode {
-        AddressingMode::Immediate => registers.set_acc(address as u8),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => registers.set_acc(memory.get_mem_cell_value(address as usize)),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => registers.set_acc(memory.get_mem_cell_value(address as usize + registers.get_x() as usize)),
-        AddressingMode::AbsoluteY => registers.set_acc(memory.get_mem_cell_value(address as usize + registers.get_y() as usize)),
-        AddressingMode::IndirectX => registers.set_acc(memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_x()))),
-        AddressingMode::IndirectY => registers.set_acc(memory.get_mem_cell_value(indirect_indexed_address(memory, address, registers.get_y()))),
-
-        _ => { }
-    }
+pub fn lda(address: u16, addressing_mode: AddressingMode, memory: &mut system::Memory, registers: &mut system::Registers) {
+    registers.set_acc(read_operand(address, addressing_mode, *registers, memory));
 }
 
-pub fn ldx(address: u16, addressing_mode: AddressingMode, memory: system::Memory, registers: &mut system::Registers) {
-    match addressing_mode {
-        AddressingMode::Immediate => registers.set_x(address as u8),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => registers.set_x(memory.get_mem_cell_value(address as usize)),
-        AddressingMode::ZeroPageY | AddressingMode::AbsoluteY => registers.set_x(memory.get_mem_cell_value(address as usize + registers.get_y() as usize)),
-
-        _ => { }
-    }
+pub fn ldx(address: u16, addressing_mode: AddressingMode, memory: &mut system::Memory, registers: &mut system::Registers) {
+    registers.set_x(read_operand(address, addressing_mode, *registers, memory));
 }
 
-pub fn ldy(address: u16, addressing_mode: AddressingMode, memory: system::Memory, registers: &mut system::Registers) {
-    match addressing_mode {
-        AddressingMode::Immediate => registers.set_y(address as u8),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => registers.set_y(memory.get_mem_cell_value(address as usize)),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => registers.set_y(memory.get_mem_cell_value(address as usize + registers.get_x() as usize)),
-
-        _ => { }
-    }
+pub fn ldy(address: u16, addressing_mode: AddressingMode, memory: &mut system::Memory, registers: &mut system::Registers) {
+    registers.set_y(read_operand(address, addressing_mode, *registers, memory));
 }
 
 pub fn jmp(label_name: String, label_names: Vec<(String, usize)>) -> usize {
@@ -336,13 +229,9 @@ pub fn jmp(label_name: String, label_names: Vec<(String, usize)>) -> usize {
 }
 
 pub fn lsr(address: u16, addressing_mode: AddressingMode, memory: &mut system::Memory, registers: &mut system::Registers) {
-    match addressing_mode {
-        AddressingMode::Implied => registers.set_acc(registers.get_acc() >> 1),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => memory.set_mem_cell_value(address as usize, memory.get_mem_cell_value(address as usize) >> 1),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => memory.set_mem_cell_value(address as usize + registers.get_x() as usize, memory.get_mem_cell_value(address as usize + registers.get_x() as usize) >> 1),
+    let shifted_value: u8 = read_operand(address, addressing_mode, *registers, memory) >> 1;
 
-        _ => { }
-    }
+    write_operand(address, addressing_mode, registers, memory, shifted_value);
 }
 
 pub fn pha(registers: system::Registers, memory: &mut system::Memory) {
@@ -388,90 +277,120 @@ pub fn plp(flags: &mut system::Flags, memory: &mut system::Memory) {
     flags.set_negative_flag((poped_value & 0b00001000) != 0);
 }
 
-pub fn ora(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: system::Memory) {
-    match addressing_mode {
-        AddressingMode::Immediate => registers.set_acc(registers.get_acc() | address as u8),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => registers.set_acc(registers.get_acc() | memory.get_mem_cell_value(address as usize)),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => registers.set_acc(registers.get_acc() | memory.get_mem_cell_value(address as usize + registers.get_x() as usize)),
-        AddressingMode::AbsoluteY => registers.set_acc(registers.get_acc() | memory.get_mem_cell_value(address as usize + registers.get_y() as usize)),
-        AddressingMode::IndirectX => registers.set_acc(registers.get_acc() | memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_x()))),
-        AddressingMode::IndirectY => registers.set_acc(registers.get_acc() | memory.get_mem_cell_value(indirect_indexed_address(memory, address, registers.get_y()))),
-
-        _ => { }
-    }
+pub fn ora(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: &mut system::Memory) {
+    registers.set_acc(registers.get_acc() | read_operand(address, addressing_mode, *registers, memory));
 }
 
-pub fn rol(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: system::Memory, flags: &mut system::Flags) {
-    let carry_flag_bit: bool = flags.get_carry_flag();
-    let mut to_rotate: u8 = 0;
-
-    match addressing_mode {
-        AddressingMode::Implied => to_rotate = registers.get_acc(),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => to_rotate = memory.get_mem_cell_value(address as usize),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => to_rotate = memory.get_mem_cell_value(address as usize + registers.get_x() as usize),
+pub fn rol(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: &mut system::Memory, flags: &mut system::Flags) {
+    let to_rotate: u8 = read_operand(address, addressing_mode, *registers, memory);
+    let rotated_value: u8 = (to_rotate << 1) | flags.get_carry_flag() as u8;
 
-        _ => { }
-    }
+    // The bit shifted out becomes the new carry, the old carry is shifted in at the other end
+    flags.set_carry_flag(to_rotate & 0b10000000 != 0);
+    write_operand(address, addressing_mode, registers, memory, rotated_value);
+}
 
-    let new_carry_flag_bit: u8 = to_rotate & 0b10000000;
+pub fn ror(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: &mut system::Memory, flags: &mut system::Flags) {
+    let to_rotate: u8 = read_operand(address, addressing_mode, *registers, memory);
+    let rotated_value: u8 = (to_rotate >> 1) | ((flags.get_carry_flag() as u8) << 7);
 
-    // Check if the value is zero and save the opposite of the value to the carry flag
-    flags.set_carry_flag(!(new_carry_flag_bit == 0));
-    registers.set_acc((to_rotate << 1) | (carry_flag_bit as u8 >> 7));
+    // The bit shifted out becomes the new carry, the old carry is shifted in at the other end
+    flags.set_carry_flag(to_rotate & 0b00000001 != 0);
+    write_operand(address, addressing_mode, registers, memory, rotated_value);
 }
 
-pub fn ror(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: system::Memory, flags: &mut system::Flags) {
-    let carry_flag_bit: bool = flags.get_carry_flag();
-    let mut to_rotate: u8 = 0;
+pub fn sbc(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, flags: &mut system::Flags, memory: &mut system::Memory) {
+    let result: u16 = (registers.get_acc() as u16).wrapping_sub(read_operand(address, addressing_mode, *registers, memory) as u16);
 
-    match addressing_mode {
-        AddressingMode::Implied => to_rotate = registers.get_acc(),
-        AddressingMode::ZeroPage | AddressingMode::Absolute => to_rotate = memory.get_mem_cell_value(address as usize),
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => to_rotate = memory.get_mem_cell_value(address as usize + registers.get_x() as usize),
+    if result > 255 {
+        flags.set_carry_flag(true);
+    }
 
-        _ => { }
+    registers.set_acc(result as u8);
+}
+
+// Base cycle counts of the real 6502, the extra cycle for crossing a page or taking a branch is not counted
+pub fn cycle_count(opcode: Opcode, addressing_mode: AddressingMode) -> u32 {
+    match opcode {
+        Opcode::LABEL | Opcode::DRW => 0,
+        Opcode::JMP => 3,
+        Opcode::PHA | Opcode::PHP => 3,
+        Opcode::PLA | Opcode::PLP => 4,
+        Opcode::JSR | Opcode::RTS | Opcode::RTI => 6,
+        Opcode::BRK => 7,
+
+        Opcode::ASL | Opcode::LSR | Opcode::ROL | Opcode::ROR | Opcode::INC | Opcode::DEC => match addressing_mode {
+            AddressingMode::Implied => 2,
+            AddressingMode::ZeroPage => 5,
+            AddressingMode::ZeroPageX | AddressingMode::Absolute => 6,
+            _ => 7
+        },
+
+        Opcode::STA | Opcode::STX | Opcode::STY => match addressing_mode {
+            AddressingMode::ZeroPage => 3,
+            AddressingMode::ZeroPageX | AddressingMode::ZeroPageY | AddressingMode::Absolute => 4,
+            AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => 5,
+            _ => 6
+        },
+
+        _ => match addressing_mode {
+            AddressingMode::ZeroPage => 3,
+            AddressingMode::ZeroPageX | AddressingMode::ZeroPageY | AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => 4,
+            AddressingMode::IndirectX => 6,
+            AddressingMode::IndirectY => 5,
+            _ => 2
+        }
     }
+}
 
-    let new_carry_flag_bit: u8 = to_rotate & 0b00000001;
+// Every instruction that touches memory resolves its operand through here so the 6502 wrap-around rules live in one place:
+// zero page indexing and the (zp,X) / (zp),Y pointer fetches stay inside page zero, absolute indexing wraps at $FFFF.
+// Modes without a memory operand (e.g. STA #$10 or INC with no operand) have no address, the instruction is then a no-op
+fn effective_address(address: u16, addressing_mode: AddressingMode, registers: system::Registers, memory: &mut system::Memory) -> Option<usize> {
+    Some(match addressing_mode {
+        AddressingMode::ZeroPage => (address as u8) as usize,
+        AddressingMode::ZeroPageX => (address as u8).wrapping_add(registers.get_x()) as usize,
+        AddressingMode::ZeroPageY => (address as u8).wrapping_add(registers.get_y()) as usize,
+        AddressingMode::Absolute => address as usize,
+        AddressingMode::AbsoluteX => address.wrapping_add(registers.get_x() as u16) as usize,
+        AddressingMode::AbsoluteY => address.wrapping_add(registers.get_y() as u16) as usize,
+        AddressingMode::IndirectX => zero_page_pointer(memory, (address as u8).wrapping_add(registers.get_x())) as usize,
+        AddressingMode::IndirectY => zero_page_pointer(memory, address as u8).wrapping_add(registers.get_y() as u16) as usize,
 
-    // Check if the value is zero and save the opposite of the value to the carry flag
-    flags.set_carry_flag(!(new_carry_flag_bit == 0));
-    registers.set_acc((to_rotate >> 1) | ((carry_flag_bit as u8) << 1));
+        _ => return None
+    })
 }
 
-pub fn sbc(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, flags: &mut system::Flags, memory: system::Memory) {
-    let mut result: u16 = registers.get_acc() as u16;
-    
+fn read_operand(address: u16, addressing_mode: AddressingMode, registers: system::Registers, memory: &mut system::Memory) -> u8 {
     match addressing_mode {
-        AddressingMode::Immediate => result -= address, 
-        AddressingMode::ZeroPage | AddressingMode::Absolute => result -= memory.get_mem_cell_value(address as usize) as u16,
-        AddressingMode::ZeroPageX | AddressingMode::AbsoluteX => result -= memory.get_mem_cell_value(address as usize + registers.get_x() as usize) as u16,
-        AddressingMode::AbsoluteY => result -= memory.get_mem_cell_value(address as usize + registers.get_y() as usize) as u16,
-        AddressingMode::IndirectX => result -= memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_x())) as u16,
-        AddressingMode::IndirectY => result -= memory.get_mem_cell_value(indexed_indirect_address(memory, address, registers.get_y())) as u16,
-
-        _ => { }
-    }
+        AddressingMode::Immediate => address as u8,
+        AddressingMode::Implied => registers.get_acc(),
 
-    if result > 255 {
-        flags.set_carry_flag(true);
+        _ => match effective_address(address, addressing_mode, registers, memory) {
+            Some(source_address) => memory.get_mem_cell_value(source_address),
+            None => 0
+        }
     }
-
-    registers.set_acc(result as u8);
 }
 
-fn indexed_indirect_address(memory: system::Memory, address: u16, x_register: u8) -> usize {
-    let low: u8 = memory.get_mem_cell_value(address as usize + x_register as usize);
-    let high: u8 = memory.get_mem_cell_value(address as usize + x_register as usize + 1);
-    
-    ((high as u16) << 8 | low as u16) as usize
+fn write_operand(address: u16, addressing_mode: AddressingMode, registers: &mut system::Registers, memory: &mut system::Memory, value: u8) {
+    match addressing_mode {
+        AddressingMode::Implied => registers.set_acc(value),
+
+        _ => {
+            if let Some(target_address) = effective_address(address, addressing_mode, *registers, memory) {
+                memory.set_mem_cell_value(target_address, value);
+            }
+        }
+    }
 }
 
-fn indirect_indexed_address(memory: system::Memory, address: u16, y_register: u8) -> usize {
-    let low: u8 = memory.get_mem_cell_value(address as usize);
-    let high: u8 = memory.get_mem_cell_value(address as usize + 1);
-    
-    (((high as u16) << 8 | low as u16) + y_register as u16) as usize
+// The high byte of the pointer is fetched from the next zero page cell, so a pointer at $FF takes its high byte from $00
+fn zero_page_pointer(memory: &mut system::Memory, pointer_address: u8) -> u16 {
+    let low: u8 = memory.get_mem_cell_value(pointer_address as usize);
+    let high: u8 = memory.get_mem_cell_value(pointer_address.wrapping_add(1) as usize);
+
+    (high as u16) << 8 | low as u16
 }
 
 fn branch(flag_to_check: bool, expected_value: bool, label_name: String, labels: Vec<(String, usize)>, current_index: usize) -> usize {
@@ -508,4 +427,96 @@ fn compare(register_value: u8, expected_value: u8, flags: &mut system::Flags) {
             flags.set_carry_flag(true);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::system::ScreenLayout;
+
+    fn test_memory() -> system::Memory {
+        system::Memory::init(0, ScreenLayout { width: system::DEFAULT_SCREEN_WIDTH, height: system::DEFAULT_SCREEN_HEIGHT, start_address: system::DEFAULT_SCREEN_MEMORY_START })
+    }
+
+    #[test]
+    fn zero_page_indexing_wraps_inside_page_zero() {
+        let mut memory: system::Memory = test_memory();
+        let mut registers: system::Registers = system::Registers::init();
+
+        registers.set_x(0x20);
+
+        assert_eq!(effective_address(0xf0, AddressingMode::ZeroPageX, registers, &mut memory), Some(0x10));
+    }
+
+    #[test]
+    fn absolute_indexing_wraps_at_the_end_of_memory() {
+        let mut memory: system::Memory = test_memory();
+        let mut registers: system::Registers = system::Registers::init();
+
+        registers.set_x(0x01);
+        registers.set_y(0x02);
+
+        assert_eq!(effective_address(0xffff, AddressingMode::AbsoluteX, registers, &mut memory), Some(0x0000));
+        assert_eq!(effective_address(0xffff, AddressingMode::AbsoluteY, registers, &mut memory), Some(0x0001));
+    }
+
+    #[test]
+    fn indirect_pointer_at_the_end_of_page_zero_takes_its_high_byte_from_zero() {
+        let mut memory: system::Memory = test_memory();
+        let mut registers: system::Registers = system::Registers::init();
+
+        memory.set_mem_cell_value(0xff, 0x34);
+        memory.set_mem_cell_value(0x00, 0x12);
+        registers.set_y(0x01);
+
+        assert_eq!(effective_address(0xff, AddressingMode::IndirectY, registers, &mut memory), Some(0x1235));
+
+        registers.set_x(0x0f);
+        memory.set_mem_cell_value(0x0e, 0x78);
+        memory.set_mem_cell_value(0x0f, 0x56);
+
+        // ($FF,X) with X = $0F reads the pointer from $0E and $0F
+        assert_eq!(effective_address(0xff, AddressingMode::IndirectX, registers, &mut memory), Some(0x5678));
+    }
+
+    #[test]
+    fn stores_and_increments_without_a_memory_operand_do_nothing() {
+        let mut memory: system::Memory = test_memory();
+        let mut registers: system::Registers = system::Registers::init();
+
+        registers.set_acc(0x42);
+        sta(0x10, AddressingMode::Immediate, registers, &mut memory);
+        inc(0xffff, AddressingMode::Implied, registers, &mut memory);
+
+        assert_eq!(effective_address(0x10, AddressingMode::Immediate, registers, &mut memory), None);
+        assert_eq!(memory.get_mem_cell_value(0x10), 0);
+        assert_eq!(registers.get_acc(), 0x42);
+    }
+
+    #[test]
+    fn rotates_write_back_to_memory_through_the_carry() {
+        let mut memory: system::Memory = test_memory();
+        let mut registers: system::Registers = system::Registers::init();
+        let mut flags: system::Flags = system::Flags::init();
+
+        memory.set_mem_cell_value(0x10, 0b1000_0001);
+        flags.set_carry_flag(true);
+        rol(0x10, AddressingMode::ZeroPage, &mut registers, &mut memory, &mut flags);
+
+        assert_eq!(memory.get_mem_cell_value(0x10), 0b0000_0011);
+        assert!(flags.get_carry_flag());
+        assert_eq!(registers.get_acc(), 0);
+
+        flags.set_carry_flag(false);
+        ror(0x10, AddressingMode::ZeroPage, &mut registers, &mut memory, &mut flags);
+
+        assert_eq!(memory.get_mem_cell_value(0x10), 0b0000_0001);
+        assert!(flags.get_carry_flag());
+
+        registers.set_acc(0b0000_0010);
+        ror(0, AddressingMode::Implied, &mut registers, &mut memory, &mut flags);
+
+        assert_eq!(registers.get_acc(), 0b1000_0001);
+        assert!(!flags.get_carry_flag());
+    }
+}
@@ -1,25 +1,53 @@
 use std::env::args;
 
 mod read_file;
+mod audio_output;
+mod video_output;
+mod config;
+mod input_script;
+mod bus;
+mod devices;
 mod analyze_code;
 mod emulator;
+mod debugger;
+mod breakpoints;
+mod gdb_stub;
 mod verifier;
 mod instruction_functions;
 mod system;
+mod font;
+mod framebuffer;
 mod window;
+mod test_runner;
 
 fn main() {
     let arguments: Vec<String> = args().collect::<Vec<String>>();
 
-    if arguments.len() != 2 {
-        println!("Please input a path to the file");
-        std::process::exit(-1);
+    if arguments.get(1).map(String::as_str) == Some("test") {
+        let passed: bool = match arguments.get(2) {
+            Some(directory) => test_runner::run_tests(directory),
+            None => {
+                println!("Please input a path to the test directory");
+                false
+            }
+        };
+
+        std::process::exit(if passed { 0 } else { 1 });
     }
+
+    let config: config::Config = match config::parse_arguments(arguments) {
+        Ok(config) => config,
+        Err(message) => {
+            println!("{message}");
+            std::process::exit(-1);
+        }
+    };
     
-    let _file_data_lines: Vec<String> = read_file::read_file(arguments[1].to_string());
+    let _file_data_lines: Vec<String> = read_file::read_file(config.file_path.clone());
     let _command_data: Vec<analyze_code::Instruction> = analyze_code::get_instructions(_file_data_lines.clone());
+    let _data_blocks: Vec<analyze_code::DataBlock> = analyze_code::get_data_blocks(_file_data_lines.clone());
     
     if verifier::verify_data(_command_data.clone()) {
-        emulator::start_emulator(_command_data);
+        emulator::start_emulator(_command_data, _data_blocks, config);
     }
 }
\ No newline at end of file
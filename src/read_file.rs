@@ -6,20 +6,24 @@ pub fn read_file(path: String) -> Vec<String> {
     let file_data_lines: Vec<String> = file_data_lines_raw.map(|p| p.to_string()).collect();
     let mut to_return: Vec<String> = vec![];
 
-    for i in 0..file_data_lines.len() {
-        if file_data_lines[i].trim().len() == 0 || file_data_lines[i].trim().chars().nth(0).unwrap() == ';' {
+    for file_data_line in file_data_lines.iter() {
+        if file_data_line.trim().is_empty() || file_data_line.trim().starts_with(';') {
             continue;
         }
 
-        if file_data_lines[i].contains(";") {
-            let to_push: &str = file_data_lines[i].split(&";".to_string()).collect::<Vec<&str>>()[0];
+        if file_data_line.contains(';') {
+            let to_push: &str = file_data_line.split(';').collect::<Vec<&str>>()[0];
             to_return.push(to_push.trim().to_string());
 
             continue;
         }
 
-        to_return.push(file_data_lines[i].clone().trim().to_string());
+        to_return.push(file_data_line.trim().to_string());
     }
 
     to_return
-}
\ No newline at end of file
+}
+
+pub fn read_binary_file(path: String) -> Vec<u8> {
+    fs::read(path).expect("Error reading file")
+}
@@ -1,4 +1,13 @@
+#[allow(clippy::module_inception)]
 pub mod system {
+    use crate::bus::{Bus, Device, Mapper};
+    use crate::config::RomWriteMode;
+    use crate::devices::ram::Ram;
+    use crate::devices::rom::Rom;
+    use crate::devices::random_number_generator::RandomNumberGenerator;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
     #[derive(Clone, Copy)]
     pub struct Registers {
         acc: u8,
@@ -97,6 +106,14 @@ pub mod system {
             self.negative = value;
         }
 
+        pub fn get_interrupt_disable_flag(&self) -> bool {
+            self.interrupt_disable
+        }
+
+        pub fn get_decimal_flag(&self) -> bool {
+            self.decimal
+        }
+
         pub fn get_carry_flag(&self) -> bool {
             self.carry
         }
@@ -114,42 +131,206 @@ pub mod system {
         }
     }
     
-    #[derive(Clone, Copy)]
+    pub const CPU_CLOCK_HZ: u32 = 1_000_000;
+
+    pub const ZERO_PAGE_START: u16 = 0x0000;
+    pub const RANDOM_NUMBER_ADDRESS: u16 = 0x00fe;
+    pub const DEFAULT_SCREEN_WIDTH: usize = 128;
+    pub const DEFAULT_SCREEN_HEIGHT: usize = 128;
+    pub const DEFAULT_SCREEN_MEMORY_START: u16 = 0x0100;
+    // Pushes start at $4200 and grow upwards, the page below it is reserved for the stack as well
+    pub const STACK_START: u16 = 0x4100;
+    pub const STACK_END: u16 = 0x42ff;
+    pub const BANK_WINDOW_START: u16 = 0x8000;
+    pub const BANK_WINDOW_END: u16 = 0xbfff;
+    pub const IO_START: u16 = 0xd000;
+    pub const IO_END: u16 = 0xdfff;
+    pub const BANK_SELECT_ADDRESS: u16 = 0xd000;
+    pub const KEYBOARD_START: u16 = 0xd010;
+    pub const KEYBOARD_END: u16 = 0xd01f;
+    pub const MOUSE_START: u16 = 0xd020;
+    pub const MOUSE_END: u16 = 0xd02f;
+    pub const JOYSTICK_ADDRESS: u16 = 0xd030;
+    pub const SOUND_START: u16 = 0xd040;
+    pub const SOUND_END: u16 = 0xd04f;
+    pub const TIMER_START: u16 = 0xd050;
+    pub const TIMER_END: u16 = 0xd05f;
+    pub const VIDEO_START: u16 = 0xd060;
+    pub const VIDEO_END: u16 = 0xd06f;
+    pub const SERIAL_START: u16 = 0xd070;
+    pub const SERIAL_END: u16 = 0xd07f;
+    pub const FILE_IO_START: u16 = 0xd080;
+    pub const FILE_IO_END: u16 = 0xd08f;
+    pub const SPRITES_START: u16 = 0xd090;
+    pub const SPRITES_END: u16 = 0xd0ff;
+    pub const PALETTE_START: u16 = 0xd100;
+    pub const PALETTE_END: u16 = 0xd3ff;
+    pub const TEXT_COLUMNS: usize = 32;
+    pub const TEXT_ROWS: usize = 24;
+    pub const TEXT_MAP_START: u16 = 0xd400;
+    pub const TEXT_COLOUR_MAP_START: u16 = 0xd700;
+    pub const CHARACTER_RAM_START: u16 = 0xda00;
+    pub const CHARACTER_RAM_END: u16 = 0xddff;
+    pub const BLITTER_START: u16 = 0xde00;
+    pub const BLITTER_END: u16 = 0xde0f;
+
+    // Size and position of the framebuffer, one byte per pixel stored row by row from the start address
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ScreenLayout {
+        pub width: usize,
+        pub height: usize,
+        pub start_address: u16
+    }
+
+    impl ScreenLayout {
+        pub fn size(&self) -> usize {
+            self.width * self.height
+        }
+
+        pub fn end_address(&self) -> u16 {
+            (self.start_address as usize + self.size() - 1) as u16
+        }
+
+        // The first fixed region the screen would cover, as its name and address range. The bank window only counts when banking is used
+        pub fn overlapped_region(&self, banked: bool) -> Option<(&'static str, u16, u16)> {
+            let mut fixed_regions: Vec<(&'static str, u16, u16)> = vec![
+                ("the random number generator", RANDOM_NUMBER_ADDRESS, RANDOM_NUMBER_ADDRESS),
+                ("the stack", STACK_START, STACK_END),
+                ("the I/O devices", IO_START, IO_END)
+            ];
+
+            if banked {
+                fixed_regions.push(("the bank window", BANK_WINDOW_START, BANK_WINDOW_END));
+            }
+
+            fixed_regions.into_iter().find(|(_, start, end)| self.start_address <= *end && self.end_address() >= *start)
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum AccessKind {
+        Read,
+        Write
+    }
+
+    // A read or write done by the program, a read has the same old and new value
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MemoryAccess {
+        pub address: u16,
+        pub kind: AccessKind,
+        pub old_value: u8,
+        pub new_value: u8
+    }
+
     pub struct Memory {
-        mem_cell: [u8; 65536],
-        stack_pointer: u16
+        mapper: Mapper,
+        screen_layout: ScreenLayout,
+        stack_pointer: u16,
+        // Handles to the read-only regions attached to the mapper, for their write faults
+        roms: Vec<Rc<RefCell<Rom>>>,
+        // Only kept while the debugger has watchpoints, peeks are never logged
+        access_log: Option<Vec<MemoryAccess>>
     }
 
     impl Memory {
-        pub fn init() -> Memory {
+        pub fn init(random_seed: u64, screen_layout: ScreenLayout) -> Memory {
+            let mut mapper: Mapper = Mapper::init();
+
+            mapper.attach(ZERO_PAGE_START, 0xffff, Box::new(Ram::init(65536)));
+            mapper.attach(screen_layout.start_address, screen_layout.end_address(), Box::new(Ram::init(screen_layout.size())));
+            mapper.attach(RANDOM_NUMBER_ADDRESS, RANDOM_NUMBER_ADDRESS, Box::new(RandomNumberGenerator::init(random_seed)));
+
             Memory { 
-                mem_cell: [00; 65536],
-                stack_pointer: 0x4200
+                mapper,
+                screen_layout,
+                stack_pointer: 0x4200,
+                roms: vec![],
+                access_log: None
             }
         }
 
-        pub fn get_mem_cell_value(&self, index: usize) -> u8 {
-            self.mem_cell[index]
+        pub fn attach_device(&mut self, start: u16, end: u16, device: Box<dyn Device>) {
+            self.mapper.attach(start, end, device);
+        }
+
+        pub fn tick(&mut self, cycles: u32) {
+            self.mapper.tick(cycles);
+        }
+
+        pub fn irq_pending(&self) -> bool {
+            self.mapper.irq_pending()
+        }
+
+        // Replaces the region with a ROM device holding its current contents
+        pub fn protect_region(&mut self, start: u16, end: u16, write_mode: RomWriteMode) {
+            let cells: Vec<u8> = (start..=end).map(|address| self.mapper.peek(address)).collect();
+            let rom: Rc<RefCell<Rom>> = Rc::new(RefCell::new(Rom::init(start, cells, write_mode)));
+
+            self.mapper.attach(start, end, Box::new(Rc::clone(&rom)));
+            self.roms.push(rom);
+        }
+
+        // Returns the address of a trapped write to a read-only region, if there was one since the last call
+        pub fn take_write_fault(&mut self) -> Option<u16> {
+            // Every ROM is asked so none keeps a stale fault for the next call
+            self.roms.iter().fold(None, |fault, rom| rom.borrow_mut().take_write_fault().or(fault))
+        }
+
+        pub fn set_access_logging(&mut self, enabled: bool) {
+            self.access_log = if enabled { Some(vec![]) } else { None };
+        }
+
+        // Returns the reads and writes since the last call, a write dropped by a read-only region has the same old and new value
+        pub fn take_accesses(&mut self) -> Vec<MemoryAccess> {
+            self.access_log.as_mut().map(std::mem::take).unwrap_or_default()
+        }
+
+        pub fn get_mem_cell_value(&mut self, index: usize) -> u8 {
+            let value: u8 = self.mapper.read(index as u16);
+
+            if let Some(access_log) = self.access_log.as_mut() {
+                access_log.push(MemoryAccess { address: index as u16, kind: AccessKind::Read, old_value: value, new_value: value });
+            }
+
+            value
+        }
+
+        // Reads without the side effects a read can have on a device, for the renderer and the debugging tools
+        pub fn peek_mem_cell_value(&self, index: usize) -> u8 {
+            self.mapper.peek(index as u16)
         }
 
         pub fn get_stack_pointer(&self) -> u16 {
             self.stack_pointer
         }
 
-        pub fn get_screen_memory(&self) -> [u8; 16384] {
-            self.mem_cell[256..16640].try_into().unwrap()
+        pub fn get_screen_layout(&self) -> ScreenLayout {
+            self.screen_layout
+        }
+
+        pub fn get_screen_memory(&self) -> Vec<u8> {
+            (0..self.screen_layout.size())
+                .map(|offset| self.mapper.peek(self.screen_layout.start_address + offset as u16))
+                .collect()
         }
 
         pub fn set_mem_cell_value(&mut self, index: usize, value: u8) {
-            self.mem_cell[index] = value;
+            let address: u16 = index as u16;
+            let old_value: Option<u8> = self.access_log.as_ref().map(|_| self.mapper.peek(address));
+
+            self.mapper.write(address, value);
+
+            if let (Some(access_log), Some(old_value)) = (self.access_log.as_mut(), old_value) {
+                access_log.push(MemoryAccess { address, kind: AccessKind::Write, old_value, new_value: self.mapper.peek(address) });
+            }
         }
 
         pub fn increment_stack_pointer(&mut self) {
-            self.stack_pointer = self.stack_pointer + 1;
+            self.stack_pointer += 1;
         }
 
         pub fn decrement_stack_pointer(&mut self) {
-            self.stack_pointer = self.stack_pointer - 1;
+            self.stack_pointer -= 1;
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,304 @@
+use crate::analyze_code::{self, DataBlock, Instruction};
+use crate::config::{self, Config};
+use crate::emulator::{self, FinalState};
+use crate::read_file;
+use crate::verifier;
+use image::{Rgba, RgbaImage};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+// Programs that set neither a cycle nor a frame budget are stopped after ten seconds of emulated time
+const DEFAULT_CYCLE_BUDGET: u64 = 10_000_000;
+
+// What a test expects from the finished run, every expectation is optional
+struct Expectations {
+    acc: Option<u8>,
+    x: Option<u8>,
+    y: Option<u8>,
+    halted: Option<bool>,
+    checksums: Vec<(u16, u16, u32)>,
+    screen: Option<String>
+}
+
+// Runs every test in the directory and prints a line per test, returns whether all of them passed.
+// A test is a manifest file ending in .test, written like a config file: expectations are the `expect-` options,
+// `program` names the program (by default the manifest name with .asm) and every other line is an emulator option.
+// Paths in the manifest are relative to the test directory, the seed is 0 unless the test sets one
+pub fn run_tests(directory: &str) -> bool {
+    let mut manifests: Vec<PathBuf> = match fs::read_dir(directory) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.extension().map_or(false, |extension| extension == "test")).collect(),
+        Err(error) => {
+            println!("Error reading the test directory {directory}: {error}");
+            return false;
+        }
+    };
+
+    manifests.sort();
+
+    let mut failed: usize = 0;
+
+    for manifest in manifests.iter() {
+        let name: String = manifest.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let failures: Vec<String> = run_test(manifest);
+
+        if failures.is_empty() {
+            println!("PASS {name}");
+        } else {
+            println!("FAIL {name}");
+            failed += 1;
+
+            for failure in failures {
+                println!("    {failure}");
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", manifests.len() - failed, failed);
+    failed == 0
+}
+
+fn run_test(manifest: &Path) -> Vec<String> {
+    let (config, expectations): (Config, Expectations) = match read_manifest(manifest) {
+        Ok(test) => test,
+        Err(message) => return vec![message]
+    };
+
+    let final_state: FinalState = match panic::catch_unwind(AssertUnwindSafe(|| run_program(config))) {
+        Ok(Some(final_state)) => final_state,
+        Ok(None) => return vec!["The program did not pass verification".to_string()],
+        Err(payload) => {
+            let message: String = payload.downcast_ref::<String>().cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|message| message.to_string()))
+                .unwrap_or_default();
+
+            return vec![format!("The emulator stopped with an error: {message}")];
+        }
+    };
+
+    // The actual and diff screens are saved next to the manifest
+    check_expectations(&manifest.with_extension("").to_string_lossy(), &expectations, &final_state)
+}
+
+fn read_manifest(manifest: &Path) -> Result<(Config, Expectations), String> {
+    let directory: &Path = manifest.parent().unwrap_or_else(|| Path::new(""));
+    let resolve = |path: &str| -> String { directory.join(path).to_string_lossy().to_string() };
+    let mut config: Config = Config::init();
+    let mut expectations: Expectations = Expectations { acc: None, x: None, y: None, halted: None, checksums: vec![], screen: None };
+
+    config.file_path = manifest.with_extension("asm").to_string_lossy().to_string();
+    config.headless = true;
+    config.serial_stdin = false;
+
+    for (option, value) in config::read_config_file(&manifest.to_string_lossy())? {
+        let value: &str = value.as_str();
+
+        match option.as_str() {
+            "program" => config.file_path = resolve(value),
+            "expect-a" => expectations.acc = Some(parse_register_value(value)?),
+            "expect-x" => expectations.x = Some(parse_register_value(value)?),
+            "expect-y" => expectations.y = Some(parse_register_value(value)?),
+            "expect-halt" => expectations.halted = Some(config::parse_switch(&option, value)?),
+            "expect-checksum" => expectations.checksums.push(parse_checksum(value)?),
+            "expect-screen" => expectations.screen = Some(resolve(value)),
+
+            _ => config::apply_option(&mut config, &option, value)?
+        }
+    }
+
+    config::check_screen_layout(&config)?;
+
+    // Every file an option names is resolved here instead of changing the working directory of the whole process
+    for path in [&mut config.bank_image, &mut config.input_script, &mut config.record_input, &mut config.wav, &mut config.serial_out,
+                 &mut config.serial_in, &mut config.file_sandbox, &mut config.record, &mut config.record_raw].into_iter().flatten() {
+        *path = resolve(path);
+    }
+
+    for (_, path) in config.screenshots.iter_mut() {
+        *path = resolve(path);
+    }
+
+    if config.seed.is_none() {
+        config.seed = Some(0);
+    }
+
+    if config.cycles.is_none() && config.frames.is_none() {
+        config.cycles = Some(DEFAULT_CYCLE_BUDGET);
+    }
+
+    Ok((config, expectations))
+}
+
+fn run_program(config: Config) -> Option<FinalState> {
+    let file_data_lines: Vec<String> = read_file::read_file(config.file_path.clone());
+    let instructions: Vec<Instruction> = analyze_code::get_instructions(file_data_lines.clone());
+    let data_blocks: Vec<DataBlock> = analyze_code::get_data_blocks(file_data_lines);
+
+    if !verifier::verify_data(instructions.clone()) {
+        return None;
+    }
+
+    Some(emulator::start_emulator(instructions, data_blocks, config))
+}
+
+fn check_expectations(name: &str, expectations: &Expectations, final_state: &FinalState) -> Vec<String> {
+    let mut failures: Vec<String> = vec![];
+    let registers: [(&str, Option<u8>, u8); 3] = [
+        ("A", expectations.acc, final_state.registers.get_acc()),
+        ("X", expectations.x, final_state.registers.get_x()),
+        ("Y", expectations.y, final_state.registers.get_y())
+    ];
+
+    for (register, expected, actual) in registers {
+        if let Some(expected) = expected {
+            if expected != actual {
+                failures.push(format!("{register} is ${actual:02X}, expected ${expected:02X}"));
+            }
+        }
+    }
+
+    if let Some(expected) = expectations.halted {
+        if expected != final_state.halted {
+            failures.push(if expected { format!("The program did not reach BRK within its budget, it ran for {} cycles", final_state.cycles) } else { "The program stopped at BRK".to_string() });
+        }
+    }
+
+    for (start, end, expected) in expectations.checksums.iter() {
+        let actual: u32 = crc32(&final_state.memory[*start as usize..=*end as usize]);
+
+        if actual != *expected {
+            failures.push(format!("The checksum of ${start:04X}-${end:04X} is {actual:08X}, expected {expected:08X}"));
+        }
+    }
+
+    if let Some(expected_path) = expectations.screen.as_ref() {
+        if let Some(failure) = compare_screen(name, expected_path, &final_state.screen) {
+            failures.push(failure);
+        }
+    }
+
+    failures
+}
+
+// Saves the actual screen next to the expected one when they differ, together with a diff image
+// that shows the matching pixels darkened and the differing pixels in magenta
+fn compare_screen(name: &str, expected_path: &str, actual: &RgbaImage) -> Option<String> {
+    let actual_path: String = format!("{name}.actual.png");
+    let diff_path: String = format!("{name}.diff.png");
+
+    let expected: RgbaImage = match image::open(expected_path) {
+        Ok(expected) => expected.to_rgba8(),
+        Err(_) => {
+            save_image(actual, &actual_path);
+            return Some(format!("The expected screen {expected_path} could not be read, the actual screen was saved to {actual_path}"));
+        }
+    };
+
+    if expected.dimensions() != actual.dimensions() {
+        save_image(actual, &actual_path);
+        return Some(format!("The screen is {}x{}, expected {}x{}, the actual screen was saved to {actual_path}", actual.width(), actual.height(), expected.width(), expected.height()));
+    }
+
+    let mut differing_pixels: usize = 0;
+    let diff: RgbaImage = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let actual_pixel: Rgba<u8> = *actual.get_pixel(x, y);
+
+        if actual_pixel == *expected.get_pixel(x, y) {
+            Rgba([actual_pixel[0] / 4, actual_pixel[1] / 4, actual_pixel[2] / 4, 255])
+        } else {
+            differing_pixels += 1;
+            Rgba([255, 0, 255, 255])
+        }
+    });
+
+    if differing_pixels == 0 {
+        return None;
+    }
+
+    save_image(actual, &actual_path);
+    save_image(&diff, &diff_path);
+
+    Some(format!("The screen differs from {expected_path} in {differing_pixels} pixels, see {actual_path} and {diff_path}"))
+}
+
+fn save_image(image: &RgbaImage, path: &str) {
+    if let Err(error) = image.save(path) {
+        println!("Error saving {path}: {error}");
+    }
+}
+
+fn parse_register_value(value: &str) -> Result<u8, String> {
+    match config::parse_address(value) {
+        Ok(register_value) if register_value <= 0xff => Ok(register_value as u8),
+        _ => Err(format!("Invalid register value {value}, register values are written as $XX"))
+    }
+}
+
+// Written as `$XXXX-$XXXX checksum` with the CRC-32 of the range in hexadecimal
+fn parse_checksum(value: &str) -> Result<(u16, u16, u32), String> {
+    let (range, checksum): (&str, &str) = value.split_once(' ').ok_or(format!("Invalid checksum {value}, expected a range and a checksum"))?;
+    let (start, end): (u16, u16) = config::parse_address_range(range)?;
+    let checksum: u32 = u32::from_str_radix(checksum.trim(), 16).map_err(|_| format!("Invalid checksum {checksum}"))?;
+
+    Ok((start, end, checksum))
+}
+
+// The CRC-32 used by zip and PNG, so checksums can be compared with the output of common tools
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffffffff;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_the_standard_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn checksums_are_a_range_and_a_hexadecimal_value() {
+        assert_eq!(parse_checksum("$0100-$017F F695E190"), Ok((0x0100, 0x017f, 0xf695e190)));
+        assert!(parse_checksum("$0100-$017F").is_err());
+        assert!(parse_checksum("$0180-$0100 F695E190").is_err());
+        assert!(parse_checksum("$0100-$017F checksum").is_err());
+    }
+
+    #[test]
+    fn manifest_paths_are_relative_to_the_manifest() {
+        let directory: PathBuf = std::env::temp_dir().join(format!("vp8-test-runner-{}", std::process::id()));
+        let manifest: PathBuf = directory.join("paths.test");
+
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(&manifest, "input-script = keys.txt\nscreenshot-at = 2 shots/two.png\nexpect-screen = screen.png\n").unwrap();
+
+        let (config, expectations): (Config, Expectations) = read_manifest(&manifest).unwrap();
+
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(config.file_path, directory.join("paths.asm").to_string_lossy());
+        assert_eq!(config.input_script, Some(directory.join("keys.txt").to_string_lossy().to_string()));
+        assert_eq!(config.screenshots[0].1, directory.join("shots/two.png").to_string_lossy());
+        assert_eq!(expectations.screen, Some(directory.join("screen.png").to_string_lossy().to_string()));
+        assert_eq!(config.seed, Some(0));
+    }
+
+    #[test]
+    fn the_example_test_passes() {
+        let manifest: &Path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/tests/count.test"));
+
+        assert_eq!(run_test(manifest), Vec::<String>::new());
+    }
+}
@@ -1,12 +1,12 @@
 use crate::analyze_code::{Instruction, Opcode, AddressingMode};
 
 pub fn verify_data(instructions: Vec<Instruction>) -> bool {
-    let _only_implied_mode: [Opcode; 25] = [
+    let _only_implied_mode: [Opcode; 26] = [
         Opcode::TXS, Opcode::TSX, Opcode::PHA, Opcode::PLA, Opcode::RTS,
         Opcode::TAX, Opcode::TXA, Opcode::DEX, Opcode::INX, Opcode::TAY, 
         Opcode::TYA, Opcode::DEY, Opcode::INY, Opcode::NOP, Opcode::CLC, 
         Opcode::SEC, Opcode::CLI, Opcode::SEI, Opcode::CLV, Opcode::CLD, 
-        Opcode::SED, Opcode::BRK, Opcode::PHP, Opcode::PLP,
+        Opcode::SED, Opcode::BRK, Opcode::PHP, Opcode::PLP, Opcode::RTI,
 
         Opcode::DRW
     ];
@@ -16,9 +16,14 @@ pub fn verify_data(instructions: Vec<Instruction>) -> bool {
         Opcode::BCS, Opcode::BNE, Opcode::BEQ, Opcode::JMP, Opcode::JSR
     ];
 
-    for _i in 0..instructions.len() {
-        let _opcode_to_check: Opcode = instructions[_i].opcode;
-        let _addressing_mode: AddressingMode = instructions[_i].addressing_mode;
+    // The emulator and the debugger start at the first instruction, so there has to be one
+    if instructions.iter().all(|instruction| instruction.opcode == Opcode::LABEL) {
+        panic!("The program has no instructions to run");
+    }
+
+    for _instruction in instructions.iter() {
+        let _opcode_to_check: Opcode = _instruction.opcode;
+        let _addressing_mode: AddressingMode = _instruction.addressing_mode;
 
         match _opcode_to_check {
             Opcode::ASL | Opcode::LSR | Opcode::ROR | Opcode::ROL => allowed_addressing_mode_check(_addressing_mode, vec![AddressingMode::Implied, AddressingMode::ZeroPage, AddressingMode::ZeroPageX, AddressingMode::Absolute, AddressingMode::AbsoluteX]),
@@ -44,7 +49,7 @@ pub fn verify_data(instructions: Vec<Instruction>) -> bool {
 }
 
 fn allowed_addressing_mode_check(_addressing_mode: AddressingMode, _allowed_addressing_modes: Vec<AddressingMode>) {
-    if _allowed_addressing_modes.contains(&_addressing_mode) == false {
+    if !_allowed_addressing_modes.contains(&_addressing_mode) {
         panic!("Current addressing mode is {_addressing_mode} while allowed addressing modes are {:?}", _allowed_addressing_modes);
     }
 }
\ No newline at end of file
@@ -0,0 +1,117 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Writes the displayed frames to an animated GIF. GIF delays are counted in hundredths of a second, so every frame is written
+// with the time to the next hundredth boundary on the refresh rate timeline, e.g. 1, 2, 2, 1, 2, 2 at 60 Hz, which keeps the
+// total duration right. A frame that repeats the previous one only makes it last longer, and above 100 Hz a frame that would
+// not reach the next hundredth is replaced by the next one
+pub struct GifRecorder {
+    encoder: GifEncoder<BufWriter<File>>,
+    refresh_rate: u64,
+    frames_seen: u64,
+    // The frame waiting to be written and the time it is shown at, in hundredths of a second
+    pending: Option<(RgbaImage, u64)>
+}
+
+impl GifRecorder {
+    pub fn create(path: String, refresh_rate: u32) -> GifRecorder {
+        let mut encoder: GifEncoder<BufWriter<File>> = GifEncoder::new_with_speed(BufWriter::new(File::create(path).expect("Error creating gif file")), 10);
+
+        encoder.set_repeat(Repeat::Infinite).expect("Error writing gif file");
+
+        GifRecorder {
+            encoder,
+            refresh_rate: refresh_rate as u64,
+            frames_seen: 0,
+            pending: None
+        }
+    }
+
+    pub fn record_frame(&mut self, image: &RgbaImage) {
+        let time: u64 = self.frames_seen * 100 / self.refresh_rate;
+
+        self.frames_seen += 1;
+
+        match self.pending.take() {
+            Some((pending_image, start)) if pending_image == *image => self.pending = Some((pending_image, start)),
+            Some((_, start)) if time == start => self.pending = Some((image.clone(), start)),
+            Some((pending_image, start)) => {
+                self.write_frame(pending_image, time - start);
+                self.pending = Some((image.clone(), time));
+            }
+            None => self.pending = Some((image.clone(), time))
+        }
+    }
+
+    fn write_frame(&mut self, image: RgbaImage, delay: u64) {
+        let frame: Frame = Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(delay as u32 * 10, 1));
+
+        self.encoder.encode_frame(frame).expect("Error writing gif file");
+    }
+}
+
+impl Drop for GifRecorder {
+    fn drop(&mut self) {
+        if let Some((image, start)) = self.pending.take() {
+            let end: u64 = self.frames_seen * 100 / self.refresh_rate;
+
+            self.write_frame(image, (end - start).max(1));
+        }
+    }
+}
+
+// Writes every displayed frame as raw RGBA bytes one after the other, which ffmpeg reads with
+// `-f rawvideo -pixel_format rgba -video_size WIDTHxHEIGHT -framerate REFRESH_RATE`
+pub struct RawFrameWriter {
+    writer: BufWriter<File>
+}
+
+impl RawFrameWriter {
+    pub fn create(path: String) -> RawFrameWriter {
+        RawFrameWriter {
+            writer: BufWriter::new(File::create(path).expect("Error creating raw frame file"))
+        }
+    }
+
+    pub fn record_frame(&mut self, image: &RgbaImage) {
+        self.writer.write_all(image.as_raw()).expect("Error writing raw frame file");
+    }
+}
+
+impl Drop for RawFrameWriter {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+    use std::path::PathBuf;
+
+    #[test]
+    fn every_frame_at_60_hz_is_written_with_the_right_total_duration() {
+        let path: PathBuf = std::env::temp_dir().join(format!("vp8-gif-{}.gif", std::process::id()));
+
+        {
+            let mut recorder: GifRecorder = GifRecorder::create(path.to_string_lossy().to_string(), 60);
+
+            for frame in 0..60 {
+                recorder.record_frame(&RgbaImage::from_pixel(4, 4, image::Rgba([frame as u8 * 4, 0, 0, 255])));
+            }
+        }
+
+        let frames: Vec<Frame> = GifDecoder::new(File::open(&path).unwrap()).unwrap().into_frames().collect_frames().unwrap();
+        let delays: Vec<u32> = frames.iter().map(|frame| frame.delay().numer_denom_ms().0 / frame.delay().numer_denom_ms().1).collect();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(frames.len(), 60);
+        assert_eq!(delays[..6], [10, 20, 20, 10, 20, 20]);
+        assert_eq!(delays.iter().sum::<u32>(), 1000);
+    }
+}
@@ -1,71 +1,145 @@
-use piston_window::{self, Event, clear, rectangle, PistonWindow, WindowSettings, EventLoop};
-
-macro_rules! pixel_coordinates {
-    ($x: expr, $y: expr) => {
-        [($x * 4) as f64, ($y * 4) as f64, ($x + 4) as f64, ($y + 4) as f64]
-    };
-}
-
-macro_rules! pixel_index {
-    ($x: expr, $y: expr) => {
-        $x + $y * 128
-    };
-}
+use image::RgbaImage;
+use piston_window::{self, Button, Event, Key, MouseButton, MouseCursorEvent, PressEvent, ReleaseEvent, clear, image as draw_image, PistonWindow, WindowSettings, EventLoop};
+use piston_window::{Filter, G2dTexture, G2dTextureContext, Texture, TextureSettings, Transformed};
+use crate::devices::{keyboard, mouse};
+use crate::input_script::InputEvent;
+use crate::system::system::ScreenLayout;
 
 pub struct Window {
     window: PistonWindow,
-    data_to_render: [u8; 16384],
+    screen_width: usize,
+    screen_height: usize,
+    pixel_scale: usize,
+    // The screen is converted to an image once per frame and drawn as a single texture scaled up to the window
+    screen_image: RgbaImage,
+    texture_context: G2dTextureContext,
+    texture: G2dTexture
 }
 
 impl Window {
-    pub fn init() -> Window {
-        let mut app_window: PistonWindow = WindowSettings::new("Virtual Processor 8", [512; 2])
+    pub fn init(refresh_rate: u32, screen_layout: ScreenLayout, pixel_scale: usize) -> Window {
+        let window_size: [u32; 2] = [(screen_layout.width * pixel_scale) as u32, (screen_layout.height * pixel_scale) as u32];
+        let mut app_window: PistonWindow = WindowSettings::new("Virtual Processor 8", window_size)
             .exit_on_esc(true)
             .resizable(false)
             .build().unwrap();
             
-        // I will be honest with you, this does not work (although I am not the first one with such problems) and that's the reason of
-        // the DRW command, just to lessen the burden from this very slow program
-        app_window.set_bench_mode(true);
+        // One render event per frame of the virtual display, the emulator runs a frame worth of cycles for every one of them
+        app_window.set_max_fps(refresh_rate as u64);
+
+        let screen_image: RgbaImage = RgbaImage::new(screen_layout.width as u32, screen_layout.height as u32);
+        let mut texture_context: G2dTextureContext = app_window.create_texture_context();
+        let texture: G2dTexture = Texture::from_image(&mut texture_context, &screen_image, &TextureSettings::new().filter(Filter::Nearest))
+            .expect("Error creating the screen texture");
 
         let to_return: Window = Window {
             window: app_window,
-            data_to_render: [0; 16384]
+            screen_width: screen_layout.width,
+            screen_height: screen_layout.height,
+            pixel_scale,
+            screen_image,
+            texture_context,
+            texture
         };
 
-        return to_return;
+        to_return
     }
 
-    pub fn set_screen_memory_data(&mut self, data: [u8; 16384]) {
-        self.data_to_render = data;
+    pub fn set_screen_image(&mut self, screen_image: RgbaImage) {
+        self.screen_image = screen_image;
     }
 
     pub fn update(&mut self, e: Event) {
-        self.window.draw_2d(&e, |c, g, _| {
+        self.texture.update(&mut self.texture_context, &self.screen_image).expect("Error updating the screen texture");
+
+        let texture: &G2dTexture = &self.texture;
+        let texture_context: &mut G2dTextureContext = &mut self.texture_context;
+        let pixel_scale: f64 = self.pixel_scale as f64;
+
+        self.window.draw_2d(&e, |c, g, device| {
+            texture_context.encoder.flush(device);
+
             clear([0.5, 0.5, 0.5, 1.0], g);
-            
-            for x in 0..128 {
-                for y in 0..128 {
-                    let rgb_data: [f32; 4] = Window::convert_bytes_to_colours(self.data_to_render[pixel_index!(x, y)]);
+            draw_image(texture, c.transform.scale(pixel_scale, pixel_scale), g);
+        });
+    }
+
+    pub fn get_window_next(&mut self) -> Option<Event> {
+        self.window.next()
+    }
+
+    // F12 saves a screenshot, the key is not passed on to the keyboard device
+    pub fn is_screenshot_request(event: &Event) -> bool {
+        event.press_args() == Some(Button::Keyboard(Key::F12))
+    }
 
-                    rectangle(rgb_data, pixel_coordinates!(x, y), c.transform, g);
+    // Translates the keyboard and mouse events of the window into the events the input devices understand
+    pub fn convert_event_to_input_events(&self, event: &Event) -> Vec<InputEvent> {
+        let mut to_return: Vec<InputEvent> = vec![];
+
+        match event.press_args() {
+            Some(Button::Keyboard(key)) => to_return.extend(Window::convert_key_to_key_code(key).map(InputEvent::KeyPress)),
+            Some(Button::Mouse(button)) => to_return.extend(Window::convert_mouse_button(button).map(InputEvent::MousePress)),
+
+            _ => { }
+        }
+
+        match event.release_args() {
+            Some(Button::Keyboard(key)) => to_return.extend(Window::convert_key_to_key_code(key).map(InputEvent::KeyRelease)),
+            Some(Button::Mouse(button)) => to_return.extend(Window::convert_mouse_button(button).map(InputEvent::MouseRelease)),
+
+            _ => { }
+        }
+
+        if let Some(position) = event.mouse_cursor_args() {
+            if let Some((x, y)) = self.convert_window_to_screen_coordinates(position) {
+                to_return.push(InputEvent::MouseMove(x, y));
+            }
+        }
+
+        to_return
+    }
+
+    // Letters and digits map to their upper case ASCII codes, keys the keyboard device does not know about are dropped
+    fn convert_key_to_key_code(key: Key) -> Option<u8> {
+        match key {
+            Key::Up => Some(keyboard::KEY_UP),
+            Key::Down => Some(keyboard::KEY_DOWN),
+            Key::Left => Some(keyboard::KEY_LEFT),
+            Key::Right => Some(keyboard::KEY_RIGHT),
+            Key::Space => Some(keyboard::KEY_SPACE),
+            Key::Return => Some(keyboard::KEY_ENTER),
+
+            _ => {
+                let code: u32 = key as u32;
+
+                match char::from_u32(code) {
+                    Some(character) if character.is_ascii_alphanumeric() => Some(character.to_ascii_uppercase() as u8),
+                    _ => None
                 }
             }
-        });
+        }
     }
 
-    pub fn get_window_next(&mut self) -> Option<Event> {
-        return self.window.next()
+    // Window positions are in window pixels, every screen pixel covers pixel_scale of them in each direction
+    fn convert_window_to_screen_coordinates(&self, position: [f64; 2]) -> Option<(u8, u8)> {
+        let x: f64 = (position[0] / self.pixel_scale as f64).floor();
+        let y: f64 = (position[1] / self.pixel_scale as f64).floor();
+
+        if x < 0.0 || y < 0.0 || x >= self.screen_width as f64 || y >= self.screen_height as f64 {
+            return None;
+        }
+
+        Some((x as u8, y as u8))
     }
 
-    fn convert_bytes_to_colours(byte_to_analyze: u8) -> [f32; 4] {
-        let colour_data: [[f32; 4]; 16] = [
-            [0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0], [1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 1.0, 1.0], 
-            [0.5, 0.0, 0.5, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [1.0, 1.0, 0.0, 1.0],
-            [1.0, 0.647, 0.0, 1.0], [0.647, 0.1647, 0.1647, 1.0], [1.0, 0.8, 0.8, 1.0], [0.4117, 0.4117, 0.4117, 1.0],
-            [0.5, 0.5, 0.5, 1.0], [0.0, 0.5, 0.0, 1.0], [0.0, 0.0, 0.5, 1.0], [0.25, 0.25, 0.25, 1.0]
-        ];
+    fn convert_mouse_button(button: MouseButton) -> Option<u8> {
+        match button {
+            MouseButton::Left => Some(mouse::BUTTON_LEFT),
+            MouseButton::Right => Some(mouse::BUTTON_RIGHT),
+            MouseButton::Middle => Some(mouse::BUTTON_MIDDLE),
 
-        return colour_data[(byte_to_analyze & 0b00001111) as usize];
+            _ => None
+        }
     }
 }
\ No newline at end of file